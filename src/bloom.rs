@@ -0,0 +1,276 @@
+use parquet::{
+    errors::{ParquetError, Result},
+    file::{
+        metadata::{ColumnChunkMetaData, ParquetMetaData, RowGroupMetaData},
+        reader::ChunkReader,
+    },
+    format::{BloomFilterAlgorithm, BloomFilterCompression, BloomFilterHash, BloomFilterHeader},
+};
+use sqlite_loadable::api;
+use sqlite_loadable::prelude::*;
+use thrift::protocol::TCompactInputProtocol;
+
+use std::rc::Rc;
+
+use crate::column_values::find_leaf;
+use crate::errors::parquet_error;
+use crate::options::ReadOptions;
+use crate::pruning::{bloom_may_match, LeafConstraint, Operator, Scalar};
+use crate::source::{open_path, Source};
+use crate::trace::Trace;
+
+/// Bytes read to decode a bloom filter header, which is shorter unless a
+/// writer adds fields to it.
+const HEADER_SIZE_ESTIMATE: usize = 64;
+
+/// Bytes in each block of a split-block bloom filter.
+const BLOCK_SIZE: usize = 32;
+
+/// Bloom filters larger than this are taken as corrupt, the format caps
+/// them at 128MiB.
+const MAX_BLOOM_FILTER_SIZE: usize = 128 * 1024 * 1024;
+
+/// Odd constants that pick the bit set in each word of a block.
+const SALT: [u32; 8] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+
+/// A column chunk's split-block bloom filter, which tells when a value
+/// isn't in the chunk, but only that it might be when it says yes.
+pub struct BloomFilter {
+    blocks: Vec<[u32; 8]>,
+}
+
+impl BloomFilter {
+    /// Reads a column chunk's bloom filter, when it has one this can check,
+    /// ie split-block, hashed with xxHash and uncompressed.
+    pub fn read<R: ChunkReader>(
+        chunks: &R,
+        chunk: &ColumnChunkMetaData,
+    ) -> Result<Option<BloomFilter>> {
+        let offset = match chunk.bloom_filter_offset() {
+            Some(offset) => u64::try_from(offset)
+                .map_err(|_| ParquetError::General("invalid bloom filter offset".to_owned()))?,
+            None => return Ok(None),
+        };
+        let available = chunks.len().saturating_sub(offset);
+        let prefix =
+            chunks.get_bytes(offset, available.min(HEADER_SIZE_ESTIMATE as u64) as usize)?;
+        let mut input = prefix.as_ref();
+        let header =
+            BloomFilterHeader::read_from_in_protocol(&mut TCompactInputProtocol::new(&mut input))
+                .map_err(|err| {
+                ParquetError::General(format!("could not decode bloom filter: {}", err))
+            })?;
+        let supported = matches!(header.algorithm, BloomFilterAlgorithm::BLOCK(_))
+            && matches!(header.hash, BloomFilterHash::XXHASH(_))
+            && matches!(header.compression, BloomFilterCompression::UNCOMPRESSED(_));
+        if !supported {
+            return Ok(None);
+        }
+        let header_size = (prefix.len() - input.len()) as u64;
+        let num_bytes = usize::try_from(header.num_bytes).unwrap_or(0);
+        if num_bytes == 0
+            || num_bytes % BLOCK_SIZE != 0
+            || num_bytes > MAX_BLOOM_FILTER_SIZE
+            || num_bytes as u64 > available - header_size
+        {
+            return Err(ParquetError::General(format!(
+                "invalid bloom filter size {}",
+                header.num_bytes
+            )));
+        }
+        let bitset = chunks.get_bytes(offset + header_size, num_bytes)?;
+        let blocks = bitset
+            .chunks_exact(BLOCK_SIZE)
+            .map(|block| {
+                let mut words = [0; 8];
+                for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+                    *word = u32::from_le_bytes(bytes.try_into().unwrap());
+                }
+                words
+            })
+            .collect();
+        Ok(Some(BloomFilter { blocks }))
+    }
+
+    /// Size of the filter's bitset.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_SIZE
+    }
+
+    /// Whether the plain-encoded `value` might be in the column chunk.
+    pub fn check(&self, value: &[u8]) -> bool {
+        let hash = xxh64(value);
+        let block = &self.blocks[(((hash >> 32) * self.blocks.len() as u64) >> 32) as usize];
+        let key = hash as u32;
+        block
+            .iter()
+            .zip(SALT)
+            .all(|(word, salt)| word & (1 << (key.wrapping_mul(salt) >> 27)) != 0)
+    }
+}
+
+/// Whether a row group could contain rows that match every equality
+/// constraint, judging from its column chunks' bloom filters, and how many
+/// bytes of filters were read to tell. When unsure, this says yes.
+pub fn row_group_may_contain<R: ChunkReader>(
+    chunks: &R,
+    row_group: &RowGroupMetaData,
+    constraints: &[LeafConstraint],
+    options: &ReadOptions,
+) -> Result<(bool, usize)> {
+    let mut bytes = 0;
+    for constraint in constraints {
+        if constraint.operator != Operator::Eq {
+            continue;
+        }
+        let chunk = row_group.column(constraint.leaf);
+        if let Some(filter) = BloomFilter::read(chunks, chunk)? {
+            bytes += filter.num_bytes();
+            if !bloom_may_match(chunk.column_descr(), &filter, constraint, options) {
+                return Ok((false, bytes));
+            }
+        }
+    }
+    Ok((true, bytes))
+}
+
+/// Whether any row group's bloom filter says the constraint's value might
+/// be in its column, or `None` when none of the column's chunks has one.
+/// Row groups without a filter might have any value.
+fn column_may_contain<R: ChunkReader>(
+    chunks: &R,
+    metadata: &ParquetMetaData,
+    constraint: &LeafConstraint,
+    options: &ReadOptions,
+) -> Result<Option<bool>> {
+    let mut has_filter = false;
+    let mut found = false;
+    for row_group in metadata.row_groups() {
+        let chunk = row_group.column(constraint.leaf);
+        match BloomFilter::read(chunks, chunk)? {
+            Some(filter) => {
+                has_filter = true;
+                found |= bloom_may_match(chunk.column_descr(), &filter, constraint, options);
+            }
+            None => found = true,
+        }
+    }
+    Ok(has_filter.then_some(found))
+}
+
+/// `parquet_bloom_check(source, column, value)`: 1 when `value` might be in
+/// the column, 0 when the bloom filters of every row group rule it out,
+/// and NULL when none of the column's chunks has a bloom filter.
+pub fn parquet_bloom_check(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+    trace: &Rc<Trace>,
+) -> sqlite_loadable::Result<()> {
+    let source = Source::from_value(values.first())?;
+    let column_path = api::value_text(&values[1])?;
+    let reader = source.open(trace)?;
+    let metadata = reader.metadata();
+    let (leaf, _) = find_leaf(
+        metadata.file_metadata().schema_descr(),
+        source.name(),
+        column_path,
+    )?;
+    let constraint = LeafConstraint {
+        leaf,
+        operator: Operator::Eq,
+        value: Scalar::from_value(&values[2]),
+    };
+    let options = ReadOptions::default();
+    let found = match &source {
+        Source::Path(path) => {
+            column_may_contain(&open_path(path)?, metadata, &constraint, &options)
+        }
+        Source::Blob(blob) => column_may_contain(blob, metadata, &constraint, &options),
+    }
+    .map_err(|err| parquet_error(source.name(), err))?;
+    match found {
+        Some(found) => api::result_bool(context, found),
+        None => api::result_null(context),
+    }
+    Ok(())
+}
+
+const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+const PRIME64_3: u64 = 0x165667B19E3779F9;
+const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn merge_round(acc: u64, value: u64) -> u64 {
+    (acc ^ round(0, value))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+/// XXH64 with a seed of 0, the hash bloom filters use.
+fn xxh64(input: &[u8]) -> u64 {
+    let mut rest = input;
+    let mut hash = if input.len() >= 32 {
+        let mut acc = [
+            PRIME64_1.wrapping_add(PRIME64_2),
+            PRIME64_2,
+            0,
+            0u64.wrapping_sub(PRIME64_1),
+        ];
+        while rest.len() >= 32 {
+            for (i, acc) in acc.iter_mut().enumerate() {
+                *acc = round(*acc, read_u64(&rest[i * 8..]));
+            }
+            rest = &rest[32..];
+        }
+        let hash = acc[0]
+            .rotate_left(1)
+            .wrapping_add(acc[1].rotate_left(7))
+            .wrapping_add(acc[2].rotate_left(12))
+            .wrapping_add(acc[3].rotate_left(18));
+        acc.iter().fold(hash, |hash, acc| merge_round(hash, *acc))
+    } else {
+        PRIME64_5
+    };
+    hash = hash.wrapping_add(input.len() as u64);
+    while rest.len() >= 8 {
+        hash = (hash ^ round(0, read_u64(rest)))
+            .rotate_left(27)
+            .wrapping_mul(PRIME64_1)
+            .wrapping_add(PRIME64_4);
+        rest = &rest[8..];
+    }
+    if rest.len() >= 4 {
+        hash = (hash ^ u64::from(read_u32(rest)).wrapping_mul(PRIME64_1))
+            .rotate_left(23)
+            .wrapping_mul(PRIME64_2)
+            .wrapping_add(PRIME64_3);
+        rest = &rest[4..];
+    }
+    for byte in rest {
+        hash = (hash ^ u64::from(*byte).wrapping_mul(PRIME64_5))
+            .rotate_left(11)
+            .wrapping_mul(PRIME64_1);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME64_3);
+    hash ^ (hash >> 32)
+}
@@ -1,15 +1,20 @@
-use parquet::file::{
-    metadata::ParquetMetaData, reader::FileReader, serialized_reader::SerializedFileReader,
-    statistics::Statistics,
-};
+use parquet::file::metadata::ParquetMetaData;
+use serde_json::json;
 use sqlite_loadable::prelude::*;
 use sqlite_loadable::{
     api,
-    table::{ConstraintOperator, IndexInfo, VTab, VTabArguments, VTabCursor},
-    BestIndexError, Error, Result,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
 };
 
-use std::{fs::File, mem, os::raw::c_int};
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::footer::{decode_chunk_extras, ChunkExtras};
+use crate::options::{ReadOptions, Uint64Overflow};
+use crate::raw::RawValue;
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::stats::{result_stat, stats_bounds};
+use crate::trace::Trace;
 
 static CREATE_SQL: &str = "CREATE TABLE x(
       source hidden, 
@@ -22,7 +27,25 @@ static CREATE_SQL: &str = "CREATE TABLE x(
       stats_min, 
       stats_max,
       stats_distinct,
-      stats_null_count
+      stats_null_count,
+      uint64_overflow hidden,
+      encodings,
+      compression text,
+      data_page_offset integer,
+      dictionary_page_offset integer,
+      index_page_offset integer,
+      file_path text,
+      file_offset integer,
+      bloom_filter_offset integer,
+      bloom_filter_length integer,
+      column_index_offset integer,
+      column_index_length integer,
+      offset_index_offset integer,
+      offset_index_length integer,
+      is_max_value_exact integer,
+      is_min_value_exact integer,
+      compression_ratio real,
+      stats_deprecated integer
     )";
 enum Columns {
     Source,
@@ -36,6 +59,24 @@ enum Columns {
     StatsMax,
     StatsDistinct,
     StatsNullCount,
+    Uint64Overflow,
+    Encodings,
+    Compression,
+    DataPageOffset,
+    DictionaryPageOffset,
+    IndexPageOffset,
+    FilePath,
+    FileOffset,
+    BloomFilterOffset,
+    BloomFilterLength,
+    ColumnIndexOffset,
+    ColumnIndexLength,
+    OffsetIndexOffset,
+    OffsetIndexLength,
+    IsMaxValueExact,
+    IsMinValueExact,
+    CompressionRatio,
+    StatsDeprecated,
 }
 fn column(index: i32) -> Option<Columns> {
     match index {
@@ -50,6 +91,24 @@ fn column(index: i32) -> Option<Columns> {
         8 => Some(Columns::StatsMax),
         9 => Some(Columns::StatsDistinct),
         10 => Some(Columns::StatsNullCount),
+        11 => Some(Columns::Uint64Overflow),
+        12 => Some(Columns::Encodings),
+        13 => Some(Columns::Compression),
+        14 => Some(Columns::DataPageOffset),
+        15 => Some(Columns::DictionaryPageOffset),
+        16 => Some(Columns::IndexPageOffset),
+        17 => Some(Columns::FilePath),
+        18 => Some(Columns::FileOffset),
+        19 => Some(Columns::BloomFilterOffset),
+        20 => Some(Columns::BloomFilterLength),
+        21 => Some(Columns::ColumnIndexOffset),
+        22 => Some(Columns::ColumnIndexLength),
+        23 => Some(Columns::OffsetIndexOffset),
+        24 => Some(Columns::OffsetIndexLength),
+        25 => Some(Columns::IsMaxValueExact),
+        26 => Some(Columns::IsMinValueExact),
+        27 => Some(Columns::CompressionRatio),
+        28 => Some(Columns::StatsDeprecated),
         _ => None,
     }
 }
@@ -58,19 +117,21 @@ fn column(index: i32) -> Option<Columns> {
 pub struct ColumnChunksTable {
     /// must be first
     base: sqlite3_vtab,
+    trace: Rc<Trace>,
 }
 
 impl<'vtab> VTab<'vtab> for ColumnChunksTable {
-    type Aux = ();
+    type Aux = Rc<Trace>;
     type Cursor = ColumnChunksCursor;
 
     fn connect(
         _db: *mut sqlite3,
-        _aux: Option<&Self::Aux>,
+        aux: Option<&Self::Aux>,
         _args: VTabArguments,
     ) -> Result<(String, ColumnChunksTable)> {
         let base: sqlite3_vtab = unsafe { mem::zeroed() };
-        let vtab = ColumnChunksTable { base };
+        let trace = trace_aux(aux)?;
+        let vtab = ColumnChunksTable { base, trace };
         // TODO db.config(VTabConfig::Innocuous)?;
         Ok((CREATE_SQL.to_owned(), vtab))
     }
@@ -78,57 +139,109 @@ impl<'vtab> VTab<'vtab> for ColumnChunksTable {
         Ok(())
     }
 
-    fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
-        let mut has_source = false;
-        for mut constraint in info.constraints() {
-            match column(constraint.column_idx()) {
-                Some(Columns::Source) => {
-                    if constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ) {
-                        constraint.set_omit(true);
-                        constraint.set_argv_index(1);
-                        has_source = true;
-                    } else {
-                        return Err(BestIndexError::Constraint);
-                    }
-                }
-                _ => todo!(),
-            }
-        }
-        if !has_source {
-            return Err(BestIndexError::Error);
-        }
-        info.set_estimated_cost(100000.0);
-        info.set_estimated_rows(100000);
-        info.set_idxnum(1);
-
-        Ok(())
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(
+            info,
+            &[Columns::Source as c_int, Columns::Uint64Overflow as c_int],
+            1,
+        )
     }
 
     fn open(&mut self) -> Result<ColumnChunksCursor> {
-        Ok(ColumnChunksCursor::new())
+        Ok(ColumnChunksCursor::new(self.trace.clone()))
     }
 }
 
+/// Values of the current column chunk that take work to get, worked out
+/// once for each row rather than for each column read.
+#[derive(Default)]
+struct ChunkRow {
+    bounds: Option<(RawValue, RawValue)>,
+    extras: ChunkExtras,
+    stats_deprecated: Option<bool>,
+    compression_ratio: Option<f64>,
+}
+
 #[repr(C)]
 pub struct ColumnChunksCursor {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
     metadata: Option<ParquetMetaData>,
+    /// Fields of each column chunk the parquet crate doesn't decode, by
+    /// row group
+    extras: Vec<Vec<ChunkExtras>>,
+    /// The file's path, for errors
+    path: String,
+    /// How stats are read, like the `parquet` table reads values
+    options: ReadOptions,
     row_group_idx: usize,
     column_idx: usize,
+    row: ChunkRow,
     eof: bool,
 }
 impl ColumnChunksCursor {
-    fn new() -> ColumnChunksCursor {
+    fn new(trace: Rc<Trace>) -> ColumnChunksCursor {
         let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
         ColumnChunksCursor {
             base,
+            trace,
             metadata: None,
+            extras: vec![],
+            path: String::new(),
+            options: ReadOptions::default(),
             row_group_idx: 0,
             column_idx: 0,
+            row: ChunkRow::default(),
             eof: false,
         }
     }
+
+    /// Moves past the end of the current row group, and any after it
+    /// without columns, then reads the column chunk it lands on.
+    fn skip_empty_row_groups(&mut self) {
+        let metadata = match self.metadata.as_ref() {
+            Some(metadata) => metadata,
+            None => return,
+        };
+        while self.row_group_idx < metadata.num_row_groups()
+            && self.column_idx >= metadata.row_group(self.row_group_idx).num_columns()
+        {
+            self.column_idx = 0;
+            self.row_group_idx += 1;
+        }
+        if self.row_group_idx >= metadata.num_row_groups() {
+            self.eof = true
+        }
+        self.read_row();
+    }
+
+    /// Reads the current column chunk's `row`.
+    fn read_row(&mut self) {
+        let column_chunk = match self.metadata.as_ref() {
+            Some(metadata) if self.row_group_idx < metadata.num_row_groups() => metadata
+                .row_group(self.row_group_idx)
+                .column(self.column_idx),
+            _ => return,
+        };
+        let extras = self
+            .extras
+            .get(self.row_group_idx)
+            .and_then(|columns| columns.get(self.column_idx))
+            .copied()
+            .unwrap_or_default();
+        self.row = ChunkRow {
+            bounds: column_chunk.statistics().and_then(stats_bounds),
+            extras,
+            stats_deprecated: column_chunk
+                .statistics()
+                .map(|_| extras.is_min_max_deprecated()),
+            // uncompressed bytes for each compressed byte
+            compression_ratio: (column_chunk.compressed_size() > 0).then(|| {
+                column_chunk.uncompressed_size() as f64 / column_chunk.compressed_size() as f64
+            }),
+        };
+    }
 }
 
 impl VTabCursor for ColumnChunksCursor {
@@ -138,33 +251,30 @@ impl VTabCursor for ColumnChunksCursor {
         _idx_str: Option<&str>,
         values: &[*mut sqlite3_value],
     ) -> Result<()> {
-        let path = api::value_text(values.get(0).unwrap())?;
-        println!("{path}");
-        let file = File::open(&path).unwrap();
-        let reader = SerializedFileReader::new(file).unwrap();
-        let metadata = reader.metadata();
-        self.metadata = Some(metadata.to_owned());
-        self.eof = false;
+        let source = Source::from_value(values.first())?;
+        self.options.uint64_overflow = match values.get(1) {
+            Some(value) => Uint64Overflow::parse("uint64_overflow", api::value_text(value)?)?,
+            None => Uint64Overflow::default(),
+        };
+        self.path = source.name().to_owned();
+        let reader = source.open(&self.trace)?;
+        self.metadata = Some(reader.metadata().to_owned());
+        self.extras =
+            decode_chunk_extras(&source.footer()?).map_err(|err| source.footer_error(err))?;
         self.column_idx = 0;
         self.row_group_idx = 0;
+        self.eof = false;
+        self.skip_empty_row_groups();
         Ok(())
     }
 
     fn next(&mut self) -> Result<()> {
-        let curr_rowgroup = self
-            .metadata
-            .as_ref()
-            .unwrap()
-            .row_group(self.row_group_idx);
-        self.column_idx += 1;
-        if self.column_idx >= curr_rowgroup.columns().len() {
-            self.column_idx = 0;
-            self.row_group_idx += 1;
-            if self.row_group_idx >= self.metadata.as_ref().unwrap().num_row_groups() {
-                self.eof = true
-            }
+        if self.metadata.is_none() {
+            self.eof = true;
+            return Ok(());
         }
-        //self.eof = self.current >= self.records.as_ref().unwrap().len();
+        self.column_idx += 1;
+        self.skip_empty_row_groups();
         Ok(())
     }
 
@@ -173,28 +283,24 @@ impl VTabCursor for ColumnChunksCursor {
     }
 
     fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
-        let row_group = self
-            .metadata
-            .as_ref()
-            .unwrap()
-            .row_group(self.row_group_idx);
+        let row_group = match self.metadata.as_ref() {
+            Some(metadata) if self.row_group_idx < metadata.num_row_groups() => {
+                metadata.row_group(self.row_group_idx)
+            }
+            _ => return Ok(()),
+        };
         let column_chunk = row_group.column(self.column_idx);
-
-        // row_group.compressed_size()
-        // row_group.num_columns()
-        // row_group.num_rows()
-
-        //self.metadata.as_ref().unwrap().num_row_groups()
-        //self.metadata.as_ref().unwrap().file_metadata().version()
-        //self.metadata.as_ref().unwrap().file_metadata().created_by()
-        //self.metadata.as_ref().unwrap().file_metadata().num_rows()
-        //self.metadata.as_ref().unwrap().file_metadata().schema()
-        //self.metadata.as_ref().unwrap().file_metadata().schema().
+        let ChunkRow {
+            bounds,
+            extras,
+            stats_deprecated,
+            compression_ratio,
+        } = &self.row;
         match column(i) {
             Some(Columns::RowGroup) => {
-                api::result_int(context, self.row_group_idx.try_into().unwrap());
+                api::result_int64(context, self.row_group_idx as i64);
             }
-            Some(Columns::Source) => (),
+            Some(Columns::Source) | Some(Columns::Uint64Overflow) => (),
             Some(Columns::ColumnName) => {
                 api::result_text(context, column_chunk.column_path().to_string().as_str())?;
             }
@@ -211,50 +317,112 @@ impl VTabCursor for ColumnChunksCursor {
                 api::result_int64(context, column_chunk.uncompressed_size());
             }
             Some(Columns::StatsMin) => {
-                if let Some(stats) = column_chunk.statistics() {
-                    match stats {
-                        Statistics::Int32(ref value) => api::result_int(context, *value.min()),
-                        Statistics::Int64(ref value) => api::result_int64(context, *value.min()),
-                        Statistics::Double(ref value) => api::result_double(context, *value.min()),
-                        Statistics::Float(ref value) => {
-                            api::result_double(context, (*value.min()).into())
-                        }
-                        Statistics::ByteArray(ref value) => {
-                            api::result_text(context, (*value.min()).as_utf8().unwrap())?
-                        }
-                        _ => (),
-                    };
+                if let Some((min, _)) = bounds {
+                    let descr = column_chunk.column_descr_ptr();
+                    result_stat(context, &descr, min.clone(), &self.options, &self.path)?;
                 }
             }
             Some(Columns::StatsMax) => {
-                if let Some(stats) = column_chunk.statistics() {
-                    match stats {
-                        Statistics::Int32(ref value) => api::result_int(context, *value.max()),
-                        Statistics::Int64(ref value) => api::result_int64(context, *value.max()),
-                        Statistics::Double(ref value) => api::result_double(context, *value.max()),
-                        Statistics::Float(ref value) => {
-                            api::result_double(context, (*value.max()).into())
-                        }
-                        Statistics::ByteArray(ref value) => {
-                            api::result_text(context, (*value.max()).as_utf8().unwrap())?
-                        }
-                        _ => (),
-                    };
+                if let Some((_, max)) = bounds {
+                    let descr = column_chunk.column_descr_ptr();
+                    result_stat(context, &descr, max.clone(), &self.options, &self.path)?;
                 }
             }
             Some(Columns::StatsDistinct) => {
                 if let Some(stats) = column_chunk.statistics() {
                     if let Some(distinct) = stats.distinct_count() {
-                        api::result_int64(context, distinct.try_into().unwrap());
+                        api::result_int64(context, distinct as i64);
                     }
                 }
             }
             Some(Columns::StatsNullCount) => {
                 if let Some(stats) = column_chunk.statistics() {
-                    api::result_int64(context, stats.null_count().try_into().unwrap());
+                    api::result_int64(context, stats.null_count() as i64);
+                }
+            }
+            Some(Columns::Encodings) => {
+                let encodings: Vec<_> = column_chunk
+                    .encodings()
+                    .iter()
+                    .map(|encoding| encoding.to_string())
+                    .collect();
+                api::result_json(context, json!(encodings))?;
+            }
+            Some(Columns::Compression) => {
+                api::result_text(context, column_chunk.compression().to_string())?;
+            }
+            Some(Columns::DataPageOffset) => {
+                api::result_int64(context, column_chunk.data_page_offset());
+            }
+            Some(Columns::DictionaryPageOffset) => {
+                if let Some(offset) = column_chunk.dictionary_page_offset() {
+                    api::result_int64(context, offset);
+                }
+            }
+            Some(Columns::IndexPageOffset) => {
+                if let Some(offset) = column_chunk.index_page_offset() {
+                    api::result_int64(context, offset);
+                }
+            }
+            Some(Columns::FilePath) => {
+                if let Some(path) = column_chunk.file_path() {
+                    api::result_text(context, path)?;
+                }
+            }
+            Some(Columns::FileOffset) => {
+                api::result_int64(context, column_chunk.file_offset());
+            }
+            Some(Columns::BloomFilterOffset) => {
+                if let Some(offset) = column_chunk.bloom_filter_offset() {
+                    api::result_int64(context, offset);
+                }
+            }
+            Some(Columns::BloomFilterLength) => {
+                if let Some(length) = extras.bloom_filter_length {
+                    api::result_int(context, length);
+                }
+            }
+            Some(Columns::ColumnIndexOffset) => {
+                if let Some(offset) = column_chunk.column_index_offset() {
+                    api::result_int64(context, offset);
+                }
+            }
+            Some(Columns::ColumnIndexLength) => {
+                if let Some(length) = column_chunk.column_index_length() {
+                    api::result_int(context, length);
+                }
+            }
+            Some(Columns::OffsetIndexOffset) => {
+                if let Some(offset) = column_chunk.offset_index_offset() {
+                    api::result_int64(context, offset);
+                }
+            }
+            Some(Columns::OffsetIndexLength) => {
+                if let Some(length) = column_chunk.offset_index_length() {
+                    api::result_int(context, length);
+                }
+            }
+            Some(Columns::IsMaxValueExact) => {
+                if let Some(exact) = extras.is_max_value_exact {
+                    api::result_bool(context, exact);
+                }
+            }
+            Some(Columns::IsMinValueExact) => {
+                if let Some(exact) = extras.is_min_value_exact {
+                    api::result_bool(context, exact);
+                }
+            }
+            Some(Columns::CompressionRatio) => {
+                if let Some(ratio) = *compression_ratio {
+                    api::result_double(context, ratio);
+                }
+            }
+            Some(Columns::StatsDeprecated) => {
+                if let Some(deprecated) = *stats_deprecated {
+                    api::result_bool(context, deprecated);
                 }
             }
-            None => todo!(),
+            None => (),
         }
         Ok(())
     }
@@ -0,0 +1,246 @@
+use parquet::{
+    file::{metadata::ParquetMetaData, reader::ChunkReader},
+    format::BoundaryOrder,
+    schema::types::SchemaDescPtr,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
+};
+
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::errors::parquet_error;
+use crate::options::{ReadOptions, Uint64Overflow};
+use crate::page_index::{read_column_index, IndexedPage};
+use crate::source::{open_path, source_best_index, trace_aux, Source};
+use crate::stats::{result_stat, stats_bounds};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      source hidden,
+      row_group integer,
+      column text,
+      page integer,
+      null_page integer,
+      min,
+      max,
+      null_count integer,
+      boundary_order text,
+      uint64_overflow hidden
+    )";
+enum Columns {
+    Source,
+    RowGroup,
+    Column,
+    Page,
+    NullPage,
+    Min,
+    Max,
+    NullCount,
+    BoundaryOrder,
+    Uint64Overflow,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Source),
+        1 => Some(Columns::RowGroup),
+        2 => Some(Columns::Column),
+        3 => Some(Columns::Page),
+        4 => Some(Columns::NullPage),
+        5 => Some(Columns::Min),
+        6 => Some(Columns::Max),
+        7 => Some(Columns::NullCount),
+        8 => Some(Columns::BoundaryOrder),
+        9 => Some(Columns::Uint64Overflow),
+        _ => None,
+    }
+}
+
+/// A page's entry in the column index of its column chunk.
+struct IndexRow {
+    row_group: usize,
+    leaf: usize,
+    /// Index of the page in its column chunk's data pages, from 0
+    page: usize,
+    entry: IndexedPage,
+    boundary_order: BoundaryOrder,
+}
+
+/// Reads the column index of every column chunk that has one.
+fn read_column_indexes<R: ChunkReader>(
+    chunks: &R,
+    metadata: &ParquetMetaData,
+    name: &str,
+) -> Result<Vec<IndexRow>> {
+    let mut rows = vec![];
+    for (row_group, row_group_metadata) in metadata.row_groups().iter().enumerate() {
+        for (leaf, chunk) in row_group_metadata.columns().iter().enumerate() {
+            let index = match read_column_index(chunks, chunk) {
+                Ok(Some(index)) => index,
+                Ok(None) => continue,
+                Err(err) => return Err(parquet_error(name, err)),
+            };
+            let boundary_order = index.boundary_order;
+            rows.extend(
+                index
+                    .pages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(page, entry)| IndexRow {
+                        row_group,
+                        leaf,
+                        page,
+                        entry,
+                        boundary_order,
+                    }),
+            );
+        }
+    }
+    Ok(rows)
+}
+
+#[repr(C)]
+pub struct ColumnIndexTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for ColumnIndexTable {
+    type Aux = Rc<Trace>;
+    type Cursor = ColumnIndexCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, ColumnIndexTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), ColumnIndexTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(
+            info,
+            &[Columns::Source as c_int, Columns::Uint64Overflow as c_int],
+            1,
+        )
+    }
+
+    fn open(&mut self) -> Result<ColumnIndexCursor> {
+        Ok(ColumnIndexCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct ColumnIndexCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    schema_descr: Option<SchemaDescPtr>,
+    rows: Vec<IndexRow>,
+    /// The file's path, for errors
+    path: String,
+    /// How min and max are read, like the `parquet` table reads values
+    options: ReadOptions,
+    row_idx: usize,
+}
+
+impl ColumnIndexCursor {
+    fn new(trace: Rc<Trace>) -> ColumnIndexCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        ColumnIndexCursor {
+            base,
+            trace,
+            schema_descr: None,
+            rows: vec![],
+            path: String::new(),
+            options: ReadOptions::default(),
+            row_idx: 0,
+        }
+    }
+}
+
+impl VTabCursor for ColumnIndexCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        self.options.uint64_overflow = match values.get(1) {
+            Some(value) => Uint64Overflow::parse("uint64_overflow", api::value_text(value)?)?,
+            None => Uint64Overflow::default(),
+        };
+        self.path = source.name().to_owned();
+        let reader = source.open(&self.trace)?;
+        let metadata = reader.metadata();
+        self.rows = match &source {
+            Source::Path(path) => read_column_indexes(&open_path(path)?, metadata, path)?,
+            Source::Blob(blob) => read_column_indexes(blob, metadata, source.name())?,
+        };
+        self.schema_descr = Some(metadata.file_metadata().schema_descr_ptr());
+        self.row_idx = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.row_idx += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row_idx >= self.rows.len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let (row, schema_descr) = match (self.rows.get(self.row_idx), &self.schema_descr) {
+            (Some(row), Some(schema_descr)) => (row, schema_descr),
+            _ => return Ok(()),
+        };
+        let descr = schema_descr.column(row.leaf);
+        let bounds = stats_bounds(&row.entry.statistics);
+        match column(i) {
+            Some(Columns::Source) | Some(Columns::Uint64Overflow) => (),
+            Some(Columns::RowGroup) => api::result_int64(context, row.row_group as i64),
+            Some(Columns::Column) => api::result_text(context, descr.path().string())?,
+            Some(Columns::Page) => api::result_int64(context, row.page as i64),
+            Some(Columns::NullPage) => api::result_bool(context, row.entry.is_null_page()),
+            Some(Columns::Min) => {
+                if let Some((min, _)) = bounds {
+                    result_stat(context, &descr, min, &self.options, &self.path)?;
+                }
+            }
+            Some(Columns::Max) => {
+                if let Some((_, max)) = bounds {
+                    result_stat(context, &descr, max, &self.options, &self.path)?;
+                }
+            }
+            Some(Columns::NullCount) => {
+                if let Some(null_count) = row.entry.null_count {
+                    api::result_int64(context, null_count);
+                }
+            }
+            Some(Columns::BoundaryOrder) => match row.boundary_order {
+                BoundaryOrder::UNORDERED => api::result_text(context, "UNORDERED")?,
+                BoundaryOrder::ASCENDING => api::result_text(context, "ASCENDING")?,
+                BoundaryOrder::DESCENDING => api::result_text(context, "DESCENDING")?,
+                order => api::result_int(context, order.0),
+            },
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.row_idx as i64)
+    }
+}
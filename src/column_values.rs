@@ -0,0 +1,292 @@
+use parquet::{
+    file::reader::FileReader,
+    schema::types::{ColumnDescPtr, SchemaDescriptor},
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Error, Result,
+};
+
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::options::ReadOptions;
+use crate::parquet::result_leaf;
+use crate::raw::{LeafScanner, LeveledValues, RawValue};
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      row_group integer,
+      row_index integer,
+      value,
+      definition_level integer,
+      repetition_level integer,
+      source hidden,
+      column hidden
+    )";
+enum Columns {
+    RowGroup,
+    RowIndex,
+    Value,
+    DefinitionLevel,
+    RepetitionLevel,
+    Source,
+    Column,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::RowGroup),
+        1 => Some(Columns::RowIndex),
+        2 => Some(Columns::Value),
+        3 => Some(Columns::DefinitionLevel),
+        4 => Some(Columns::RepetitionLevel),
+        5 => Some(Columns::Source),
+        6 => Some(Columns::Column),
+        _ => None,
+    }
+}
+
+#[repr(C)]
+pub struct ColumnValuesTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for ColumnValuesTable {
+    type Aux = Rc<Trace>;
+    type Cursor = ColumnValuesCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, ColumnValuesTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), ColumnValuesTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(
+            info,
+            &[Columns::Source as c_int, Columns::Column as c_int],
+            2,
+        )
+    }
+
+    fn open(&mut self) -> Result<ColumnValuesCursor> {
+        Ok(ColumnValuesCursor::new(self.trace.clone()))
+    }
+}
+
+/// Finds the leaf column at `column_path`, a dotted path like
+/// `address.zip` or `tags.list.item`.
+pub fn find_leaf(
+    schema_descr: &SchemaDescriptor,
+    path: &str,
+    column_path: &str,
+) -> Result<(usize, ColumnDescPtr)> {
+    let parts: Vec<String> = column_path.split('.').map(str::to_owned).collect();
+    let error = |message: &str| {
+        Error::new_message(
+            format!(
+                "parquet file '{}': column '{}' {}",
+                path, column_path, message
+            )
+            .as_str(),
+        )
+    };
+    let mut is_group = false;
+    for leaf in 0..schema_descr.num_columns() {
+        let descr = schema_descr.column(leaf);
+        let leaf_parts = descr.path().parts();
+        if leaf_parts == parts.as_slice() {
+            return Ok((leaf, descr));
+        }
+        is_group |= leaf_parts.len() > parts.len() && leaf_parts.starts_with(&parts);
+    }
+    if is_group {
+        Err(error("isn't a leaf column"))
+    } else {
+        Err(error("doesn't exist"))
+    }
+}
+
+#[repr(C)]
+pub struct ColumnValuesCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    source: Option<Source>,
+    /// The file's path, or a name for a blob
+    path: String,
+    column_path: String,
+    reader: Option<Box<dyn FileReader>>,
+    descr: Option<ColumnDescPtr>,
+    scanner: Option<LeafScanner>,
+    /// The current batch, its row group, and the next level to read
+    batch: LeveledValues,
+    row_group: i64,
+    level: usize,
+    next_value: usize,
+    /// The current level
+    row_index: i64,
+    definition_level: i16,
+    repetition_level: i16,
+    value: Option<RawValue>,
+    rowid: i64,
+    eof: bool,
+}
+
+impl ColumnValuesCursor {
+    fn new(trace: Rc<Trace>) -> ColumnValuesCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        ColumnValuesCursor {
+            base,
+            trace,
+            source: None,
+            path: String::new(),
+            column_path: String::new(),
+            reader: None,
+            descr: None,
+            scanner: None,
+            batch: LeveledValues::default(),
+            row_group: -1,
+            level: 0,
+            next_value: 0,
+            row_index: -1,
+            definition_level: 0,
+            repetition_level: 0,
+            value: None,
+            rowid: -1,
+            eof: true,
+        }
+    }
+
+    /// Reads the next batch of levels, returning false when there are none
+    /// left.
+    fn next_batch(&mut self) -> Result<bool> {
+        let (reader, scanner) = match (self.reader.as_ref(), self.scanner.as_mut()) {
+            (Some(reader), Some(scanner)) => (reader, scanner),
+            _ => return Ok(false),
+        };
+        match scanner.next_batch(reader.as_ref(), &self.path)? {
+            Some((row_group, batch)) => {
+                self.row_group = row_group as i64;
+                self.batch = batch;
+                self.level = 0;
+                self.next_value = 0;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl VTabCursor for ColumnValuesCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        let column_path = match values.get(1) {
+            Some(value) => api::value_text(value)?,
+            None => return Err(Error::new_message("parquet column path is required")),
+        };
+        let reader = source.open(&self.trace)?;
+        self.path = source.name().to_owned();
+        let (leaf, descr) = find_leaf(
+            reader.metadata().file_metadata().schema_descr(),
+            &self.path,
+            column_path,
+        )?;
+        self.scanner = Some(LeafScanner::new(leaf, descr.max_def_level()));
+        self.descr = Some(descr);
+        self.source = Some(source);
+        self.column_path = column_path.to_owned();
+        self.reader = Some(reader);
+        self.batch = LeveledValues::default();
+        self.level = 0;
+        self.row_index = -1;
+        self.rowid = -1;
+        self.eof = false;
+        self.next()
+    }
+
+    fn next(&mut self) -> Result<()> {
+        let max_def_level = match self.descr.as_ref() {
+            Some(descr) => descr.max_def_level(),
+            None => {
+                self.eof = true;
+                return Ok(());
+            }
+        };
+        if self.level >= self.batch.def_levels.len() && !self.next_batch()? {
+            self.eof = true;
+            return Ok(());
+        }
+        let level = self.level;
+        self.level += 1;
+        self.definition_level = self.batch.def_levels[level];
+        self.repetition_level = self.batch.rep_levels[level];
+        // a repetition level of 0 starts a new row
+        if self.repetition_level == 0 {
+            self.row_index += 1;
+        }
+        self.value = None;
+        if self.definition_level == max_def_level {
+            self.value = self.batch.values.get(self.next_value).cloned();
+            self.next_value += 1;
+        }
+        self.rowid += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.eof
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        match column(i) {
+            Some(Columns::RowGroup) => api::result_int64(context, self.row_group),
+            Some(Columns::RowIndex) => api::result_int64(context, self.row_index),
+            Some(Columns::Value) => match (&self.value, &self.descr) {
+                (Some(value), Some(descr)) => result_leaf(
+                    context,
+                    descr,
+                    value.clone(),
+                    &ReadOptions::default(),
+                    &self.path,
+                    &self.column_path,
+                )?,
+                _ => api::result_null(context),
+            },
+            Some(Columns::DefinitionLevel) => {
+                api::result_int(context, self.definition_level.into())
+            }
+            Some(Columns::RepetitionLevel) => {
+                api::result_int(context, self.repetition_level.into())
+            }
+            Some(Columns::Source) => {
+                if let Some(source) = &self.source {
+                    source.result(context)?;
+                }
+            }
+            Some(Columns::Column) => api::result_text(context, &self.column_path)?,
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.rowid)
+    }
+}
@@ -0,0 +1,213 @@
+use parquet::{
+    basic::{ConvertedType, LogicalType, Type as PhysicalType},
+    schema::types::SchemaDescPtr,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
+};
+
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::schema::logical_type_name;
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      source hidden,
+      name text,
+      path text,
+      physical_type text,
+      logical_type text,
+      converted_type text,
+      repetition text,
+      max_definition_level integer,
+      max_repetition_level integer,
+      type_length integer,
+      precision integer,
+      scale integer,
+      field_id integer
+    )";
+enum Columns {
+    Source,
+    Name,
+    Path,
+    PhysicalType,
+    LogicalType,
+    ConvertedType,
+    Repetition,
+    MaxDefinitionLevel,
+    MaxRepetitionLevel,
+    TypeLength,
+    Precision,
+    Scale,
+    FieldId,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Source),
+        1 => Some(Columns::Name),
+        2 => Some(Columns::Path),
+        3 => Some(Columns::PhysicalType),
+        4 => Some(Columns::LogicalType),
+        5 => Some(Columns::ConvertedType),
+        6 => Some(Columns::Repetition),
+        7 => Some(Columns::MaxDefinitionLevel),
+        8 => Some(Columns::MaxRepetitionLevel),
+        9 => Some(Columns::TypeLength),
+        10 => Some(Columns::Precision),
+        11 => Some(Columns::Scale),
+        12 => Some(Columns::FieldId),
+        _ => None,
+    }
+}
+
+#[repr(C)]
+pub struct ColumnsTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for ColumnsTable {
+    type Aux = Rc<Trace>;
+    type Cursor = ColumnsCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, ColumnsTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), ColumnsTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(info, &[Columns::Source as c_int], 1)
+    }
+
+    fn open(&mut self) -> Result<ColumnsCursor> {
+        Ok(ColumnsCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct ColumnsCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    schema_descr: Option<SchemaDescPtr>,
+    leaf: usize,
+}
+
+impl ColumnsCursor {
+    fn new(trace: Rc<Trace>) -> ColumnsCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        ColumnsCursor {
+            base,
+            trace,
+            schema_descr: None,
+            leaf: 0,
+        }
+    }
+}
+
+impl VTabCursor for ColumnsCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        let reader = source.open(&self.trace)?;
+        self.schema_descr = Some(reader.metadata().file_metadata().schema_descr_ptr());
+        self.leaf = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.leaf += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        match self.schema_descr.as_ref() {
+            Some(schema_descr) => self.leaf >= schema_descr.num_columns(),
+            None => true,
+        }
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let descr = match self.schema_descr.as_ref() {
+            Some(schema_descr) => schema_descr.column(self.leaf),
+            None => return Ok(()),
+        };
+        let field = descr.self_type();
+        let info = field.get_basic_info();
+        let converted_type = Some(info.converted_type()).filter(|t| *t != ConvertedType::NONE);
+        let type_length = (descr.physical_type() == PhysicalType::FIXED_LEN_BYTE_ARRAY)
+            .then(|| descr.type_length());
+        // precision and scale are only set for decimals
+        let is_decimal = converted_type == Some(ConvertedType::DECIMAL)
+            || matches!(info.logical_type(), Some(LogicalType::Decimal { .. }));
+        let precision_scale = is_decimal.then(|| (descr.type_precision(), descr.type_scale()));
+        match column(i) {
+            Some(Columns::Source) => (),
+            Some(Columns::Name) => api::result_text(context, descr.name())?,
+            Some(Columns::Path) => api::result_text(context, descr.path().string())?,
+            Some(Columns::PhysicalType) => {
+                api::result_text(context, descr.physical_type().to_string())?
+            }
+            Some(Columns::LogicalType) => {
+                if let Some(logical_type) = info.logical_type() {
+                    api::result_text(context, logical_type_name(&logical_type))?;
+                }
+            }
+            Some(Columns::ConvertedType) => {
+                if let Some(converted_type) = converted_type {
+                    api::result_text(context, converted_type.to_string())?;
+                }
+            }
+            Some(Columns::Repetition) => api::result_text(context, info.repetition().to_string())?,
+            Some(Columns::MaxDefinitionLevel) => {
+                api::result_int(context, descr.max_def_level().into())
+            }
+            Some(Columns::MaxRepetitionLevel) => {
+                api::result_int(context, descr.max_rep_level().into())
+            }
+            Some(Columns::TypeLength) => {
+                if let Some(type_length) = type_length {
+                    api::result_int(context, type_length);
+                }
+            }
+            Some(Columns::Precision) => {
+                if let Some((precision, _)) = precision_scale {
+                    api::result_int(context, precision);
+                }
+            }
+            Some(Columns::Scale) => {
+                if let Some((_, scale)) = precision_scale {
+                    api::result_int(context, scale);
+                }
+            }
+            Some(Columns::FieldId) => {
+                if let Some(id) = info.has_id().then(|| info.id()) {
+                    api::result_int(context, id);
+                }
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.leaf as i64)
+    }
+}
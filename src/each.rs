@@ -0,0 +1,384 @@
+use parquet::{
+    basic::{ConvertedType, LogicalType, Repetition},
+    file::reader::FileReader,
+    schema::types::{ColumnDescPtr, Type},
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Error, Result,
+};
+
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::options::ReadOptions;
+use crate::parquet::result_leaf;
+use crate::raw::{LeafScanner, LeveledValues, RawValue};
+use crate::schema::find_field;
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      row_index integer,
+      position integer,
+      key,
+      value,
+      source hidden,
+      column_path hidden
+    )";
+enum Columns {
+    RowIndex,
+    Position,
+    Key,
+    Value,
+    Source,
+    ColumnPath,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::RowIndex),
+        1 => Some(Columns::Position),
+        2 => Some(Columns::Key),
+        3 => Some(Columns::Value),
+        4 => Some(Columns::Source),
+        5 => Some(Columns::ColumnPath),
+        _ => None,
+    }
+}
+
+#[repr(C)]
+pub struct EachTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for EachTable {
+    type Aux = Rc<Trace>;
+    type Cursor = EachCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, EachTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), EachTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(
+            info,
+            &[Columns::Source as c_int, Columns::ColumnPath as c_int],
+            2,
+        )
+    }
+
+    fn open(&mut self) -> Result<EachCursor> {
+        Ok(EachCursor::new(self.trace.clone()))
+    }
+}
+
+/// The leaf columns of a list or map, and the definition level its
+/// entries start at. Lower levels are a NULL or empty list.
+struct Entries {
+    value: ColumnDescPtr,
+    key: Option<ColumnDescPtr>,
+    value_leaf: usize,
+    key_leaf: Option<usize>,
+    entry_def_level: i16,
+}
+
+/// Finds the leaves of the list or map at `column_path`, a dotted path
+/// like `address.tags`.
+fn find_entries(reader: &dyn FileReader, path: &str, column_path: &str) -> Result<Entries> {
+    let schema_descr = reader.metadata().file_metadata().schema_descr();
+    let parts: Vec<String> = column_path.split('.').map(str::to_owned).collect();
+    let error = |message: &str| {
+        Error::new_message(
+            format!(
+                "parquet file '{}': column '{}' {}",
+                path, column_path, message
+            )
+            .as_str(),
+        )
+    };
+    let field =
+        find_field(schema_descr.root_schema(), &parts).ok_or_else(|| error("doesn't exist"))?;
+    let info = field.get_basic_info();
+    let is_map = matches!(
+        info.converted_type(),
+        ConvertedType::MAP | ConvertedType::MAP_KEY_VALUE
+    ) || info.logical_type() == Some(LogicalType::Map);
+    let is_list = info.converted_type() == ConvertedType::LIST
+        || info.logical_type() == Some(LogicalType::List)
+        || info.repetition() == Repetition::REPEATED;
+    let leaves: Vec<usize> = (0..schema_descr.num_columns())
+        .filter(|leaf| {
+            schema_descr
+                .column(*leaf)
+                .path()
+                .parts()
+                .starts_with(&parts)
+        })
+        .collect();
+    let (key_leaf, value_leaf) = match (is_map, is_list, leaves.as_slice()) {
+        (true, _, [key, value]) => (Some(*key), *value),
+        (true, _, _) => return Err(error("isn't a map of primitive keys and values")),
+        (false, true, [value]) => (None, *value),
+        (false, true, _) => return Err(error("isn't a list of primitive values")),
+        _ => return Err(error("isn't a list or a map")),
+    };
+    let value = schema_descr.column(value_leaf);
+    if value.max_rep_level() != 1 {
+        return Err(error("is inside another list, which isn't supported"));
+    }
+    Ok(Entries {
+        entry_def_level: entry_def_level(schema_descr.root_schema(), value.path().parts()),
+        key: key_leaf.map(|leaf| schema_descr.column(leaf)),
+        value,
+        value_leaf,
+        key_leaf,
+    })
+}
+
+/// The definition level of the repeated field on the way down `path` to a
+/// leaf, the level its list's or map's entries start at.
+fn entry_def_level(schema: &Type, path: &[String]) -> i16 {
+    let mut level = 0;
+    let mut field = schema;
+    for name in path {
+        field = match field.get_fields().iter().find(|f| f.name() == name) {
+            Some(child) => child,
+            None => break,
+        };
+        match field.get_basic_info().repetition() {
+            Repetition::REQUIRED => (),
+            Repetition::OPTIONAL => level += 1,
+            Repetition::REPEATED => return level + 1,
+        }
+    }
+    level
+}
+
+#[repr(C)]
+pub struct EachCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    source: Option<Source>,
+    /// The file's path, for errors
+    path: String,
+    column_path: String,
+    reader: Option<Box<dyn FileReader>>,
+    entries: Option<Entries>,
+    values: Option<LeafScanner>,
+    keys: Option<LeafScanner>,
+    /// The current batch of values and keys, and the next level to read
+    value_batch: LeveledValues,
+    key_batch: LeveledValues,
+    level: usize,
+    next_value: usize,
+    next_key: usize,
+    /// The current entry
+    row_index: i64,
+    position: i64,
+    key: Option<RawValue>,
+    value: Option<RawValue>,
+    rowid: i64,
+    eof: bool,
+}
+
+impl EachCursor {
+    fn new(trace: Rc<Trace>) -> EachCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        EachCursor {
+            base,
+            trace,
+            source: None,
+            path: String::new(),
+            column_path: String::new(),
+            reader: None,
+            entries: None,
+            values: None,
+            keys: None,
+            value_batch: LeveledValues::default(),
+            key_batch: LeveledValues::default(),
+            level: 0,
+            next_value: 0,
+            next_key: 0,
+            row_index: -1,
+            position: -1,
+            key: None,
+            value: None,
+            rowid: -1,
+            eof: true,
+        }
+    }
+
+    /// Reads the next batch of values, and keys for maps, returning false
+    /// when there are none left. Keys and values have the same levels, so
+    /// their batches line up.
+    fn next_batch(&mut self) -> Result<bool> {
+        let (reader, values) = match (self.reader.as_ref(), self.values.as_mut()) {
+            (Some(reader), Some(values)) => (reader, values),
+            _ => return Ok(false),
+        };
+        self.value_batch = match values.next_batch(reader.as_ref(), &self.path)? {
+            Some((_, batch)) => batch,
+            None => return Ok(false),
+        };
+        if let Some(keys) = self.keys.as_mut() {
+            if let Some((_, batch)) = keys.next_batch(reader.as_ref(), &self.path)? {
+                self.key_batch = batch;
+            }
+        }
+        self.level = 0;
+        self.next_value = 0;
+        self.next_key = 0;
+        Ok(true)
+    }
+
+    /// Returns a key or value the way the `parquet` table would.
+    fn result_value(
+        &self,
+        context: *mut sqlite3_context,
+        descr: &ColumnDescPtr,
+        value: &Option<RawValue>,
+    ) -> Result<()> {
+        match value {
+            Some(value) => result_leaf(
+                context,
+                descr,
+                value.clone(),
+                &ReadOptions::default(),
+                &self.path,
+                &self.column_path,
+            ),
+            None => {
+                api::result_null(context);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl VTabCursor for EachCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        let column_path = match values.get(1) {
+            Some(value) => api::value_text(value)?,
+            None => return Err(Error::new_message("parquet column path is required")),
+        };
+        let reader = source.open(&self.trace)?;
+        let entries = find_entries(reader.as_ref(), source.name(), column_path)?;
+        self.values = Some(LeafScanner::new(
+            entries.value_leaf,
+            entries.value.max_def_level(),
+        ));
+        self.keys = entries
+            .key
+            .as_ref()
+            .zip(entries.key_leaf)
+            .map(|(key, leaf)| LeafScanner::new(leaf, key.max_def_level()));
+        self.entries = Some(entries);
+        self.path = source.name().to_owned();
+        self.source = Some(source);
+        self.column_path = column_path.to_owned();
+        self.reader = Some(reader);
+        self.value_batch = LeveledValues::default();
+        self.key_batch = LeveledValues::default();
+        self.level = 0;
+        self.row_index = -1;
+        self.rowid = -1;
+        self.eof = false;
+        self.next()
+    }
+
+    fn next(&mut self) -> Result<()> {
+        let (entry_def_level, max_value, max_key) = match self.entries.as_ref() {
+            Some(entries) => (
+                entries.entry_def_level,
+                entries.value.max_def_level(),
+                entries.key.as_ref().map_or(0, |key| key.max_def_level()),
+            ),
+            None => {
+                self.eof = true;
+                return Ok(());
+            }
+        };
+        loop {
+            if self.level >= self.value_batch.def_levels.len() {
+                if !self.next_batch()? {
+                    self.eof = true;
+                    return Ok(());
+                }
+                continue;
+            }
+            let level = self.level;
+            self.level += 1;
+            if self.value_batch.rep_levels[level] == 0 {
+                self.row_index += 1;
+                self.position = -1;
+            }
+            self.value = None;
+            if self.value_batch.def_levels[level] == max_value {
+                self.value = self.value_batch.values.get(self.next_value).cloned();
+                self.next_value += 1;
+            }
+            self.key = None;
+            if self.key_batch.def_levels.get(level) == Some(&max_key) {
+                self.key = self.key_batch.values.get(self.next_key).cloned();
+                self.next_key += 1;
+            }
+            // lower levels are a NULL or empty list, which has no entries
+            if self.value_batch.def_levels[level] >= entry_def_level {
+                self.position += 1;
+                self.rowid += 1;
+                return Ok(());
+            }
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.eof
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let entries = match self.entries.as_ref() {
+            Some(entries) => entries,
+            None => return Ok(()),
+        };
+        match column(i) {
+            Some(Columns::RowIndex) => api::result_int64(context, self.row_index),
+            Some(Columns::Position) => api::result_int64(context, self.position),
+            Some(Columns::Key) => match &entries.key {
+                Some(descr) => self.result_value(context, descr, &self.key)?,
+                None => api::result_null(context),
+            },
+            Some(Columns::Value) => self.result_value(context, &entries.value, &self.value)?,
+            Some(Columns::Source) => {
+                if let Some(source) = &self.source {
+                    source.result(context)?;
+                }
+            }
+            Some(Columns::ColumnPath) => api::result_text(context, &self.column_path)?,
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.rowid)
+    }
+}
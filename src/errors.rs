@@ -0,0 +1,98 @@
+use parquet::errors::ParquetError;
+use sqlite_loadable::Error;
+
+use std::{
+    any::Any,
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    sync::Once,
+    thread,
+};
+
+/// A short name for the kind of a parquet error, for error messages.
+fn kind(error: &ParquetError) -> &'static str {
+    match error {
+        ParquetError::General(_) => "invalid file",
+        ParquetError::NYI(_) => "unsupported feature",
+        ParquetError::EOF(_) => "unexpected end of file",
+        ParquetError::ArrowError(_) => "arrow error",
+        ParquetError::IndexOutOfBound(_, _) => "index out of bounds",
+    }
+}
+
+/// An error while reading the parquet file at `path`, ex
+/// `error reading parquet file 'a.parquet': unexpected end of file: ...`.
+pub fn parquet_error(path: &str, error: ParquetError) -> Error {
+    let message = match &error {
+        ParquetError::General(message)
+        | ParquetError::NYI(message)
+        | ParquetError::EOF(message)
+        | ParquetError::ArrowError(message) => message.to_owned(),
+        ParquetError::IndexOutOfBound(index, bound) => format!("{} >= {}", index, bound),
+    };
+    Error::new_message(
+        format!(
+            "error reading parquet file '{}': {}: {}",
+            path,
+            kind(&error),
+            message
+        )
+        .as_str(),
+    )
+}
+
+/// An error about a value in a column of the parquet file at `path`.
+pub fn column_error(path: &str, column: &str, message: &str) -> Error {
+    Error::new_message(
+        format!(
+            "error reading parquet file '{}': column '{}': {}",
+            path, column, message
+        )
+        .as_str(),
+    )
+}
+
+thread_local! {
+    /// Whether this thread is inside `catch_panic`, where panics are caught
+    /// and shouldn't be reported.
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+static SILENCE_HOOK: Once = Once::new();
+
+/// Runs `f`, catching a panic instead of letting it unwind into SQLite.
+/// The default panic hook would print the panic and a backtrace to the
+/// host's stderr first, so while `f` runs, panics on this thread skip the
+/// hook. Panics anywhere else still go to the hook that was installed.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> thread::Result<T> {
+    SILENCE_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(Cell::get) {
+                hook(info);
+            }
+        }));
+    });
+    let outer = CATCHING.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(outer));
+    result
+}
+
+/// The parquet record reader panics on data it can't decode, like corrupt
+/// pages or unsupported types, instead of returning an error. This turns
+/// a caught panic into an error, so it never unwinds into SQLite.
+pub fn decode_error(path: &str, row_group: usize, payload: Box<dyn Any + Send>) -> Error {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown error".to_owned());
+    Error::new_message(
+        format!(
+            "error reading parquet file '{}': could not decode row group {}: {}",
+            path, row_group, message
+        )
+        .as_str(),
+    )
+}
@@ -0,0 +1,465 @@
+use bytes::{Buf, Bytes};
+use parquet::{
+    errors::{ParquetError, Result},
+    file::{
+        reader::{ChunkReader, Length},
+        FOOTER_SIZE,
+    },
+    format::FileMetaData,
+};
+use thrift::{
+    protocol::{
+        TCompactInputProtocol, TCompactOutputProtocol, TFieldIdentifier, TInputProtocol,
+        TOutputProtocol, TStructIdentifier, TType,
+    },
+    ProtocolError, ProtocolErrorKind,
+};
+
+use std::io::Read;
+
+use crate::source::read_footer;
+
+/// What the parquet crate drops when it decodes a column chunk's metadata:
+/// fields from newer versions of the format than it knows, and which of
+/// the min/max fields the statistics were in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkExtras {
+    pub bloom_filter_length: Option<i32>,
+    pub is_max_value_exact: Option<bool>,
+    pub is_min_value_exact: Option<bool>,
+    /// Whether the statistics have the legacy `min`/`max` fields, and
+    /// the `min_value`/`max_value` fields that replaced them
+    pub has_legacy_min_max: bool,
+    pub has_min_max_value: bool,
+}
+
+impl ChunkExtras {
+    /// Whether the min/max are only in the legacy fields, which sorted
+    /// byte arrays and unsigned integers as signed.
+    pub fn is_min_max_deprecated(&self) -> bool {
+        self.has_legacy_min_max && !self.has_min_max_value
+    }
+}
+
+/// Decodes the thrift `FileMetaData` of a footer.
+pub fn decode_file_metadata(footer: &[u8]) -> thrift::Result<FileMetaData> {
+    match strip_unknown_logical_types(footer)? {
+        Some(footer) => {
+            FileMetaData::read_from_in_protocol(&mut TCompactInputProtocol::new(footer.as_slice()))
+        }
+        None => FileMetaData::read_from_in_protocol(&mut TCompactInputProtocol::new(footer)),
+    }
+}
+
+/// How deeply nested a value [`skip`] skips over can be.
+const MAX_SKIP_DEPTH: i8 = 32;
+
+/// Skips over a value. The protocol's own `skip` reads binary fields as
+/// UTF-8 strings, so it fails on binary min/max statistics.
+fn skip(protocol: &mut dyn TInputProtocol, field_type: TType, depth: i8) -> thrift::Result<()> {
+    if depth <= 0 {
+        return Err(thrift::Error::Protocol(ProtocolError::new(
+            ProtocolErrorKind::DepthLimit,
+            "metadata is nested too deeply",
+        )));
+    }
+    match field_type {
+        TType::String => {
+            protocol.read_bytes()?;
+        }
+        TType::Struct => read_struct(protocol, &mut |protocol, _, field_type| {
+            skip(protocol, field_type, depth - 1)?;
+            Ok(true)
+        })?,
+        TType::List => {
+            let list = protocol.read_list_begin()?;
+            for _ in 0..list.size {
+                skip(protocol, list.element_type, depth - 1)?;
+            }
+            protocol.read_list_end()?;
+        }
+        TType::Set => {
+            let set = protocol.read_set_begin()?;
+            for _ in 0..set.size {
+                skip(protocol, set.element_type, depth - 1)?;
+            }
+            protocol.read_set_end()?;
+        }
+        TType::Map => {
+            let map = protocol.read_map_begin()?;
+            for _ in 0..map.size {
+                if let (Some(key_type), Some(value_type)) = (map.key_type, map.value_type) {
+                    skip(protocol, key_type, depth - 1)?;
+                    skip(protocol, value_type, depth - 1)?;
+                }
+            }
+            protocol.read_map_end()?;
+        }
+        field_type => protocol.skip(field_type)?,
+    }
+    Ok(())
+}
+
+/// Reads a struct's fields, calling `read` with each field's id and type.
+/// `read` returns false for fields it doesn't read, which are skipped.
+fn read_struct(
+    protocol: &mut dyn TInputProtocol,
+    read: &mut dyn FnMut(&mut dyn TInputProtocol, i16, TType) -> thrift::Result<bool>,
+) -> thrift::Result<()> {
+    protocol.read_struct_begin()?;
+    loop {
+        let field = protocol.read_field_begin()?;
+        if field.field_type == TType::Stop {
+            break;
+        }
+        if !read(protocol, field.id.unwrap_or(0), field.field_type)? {
+            skip(protocol, field.field_type, MAX_SKIP_DEPTH)?;
+        }
+        protocol.read_field_end()?;
+    }
+    protocol.read_struct_end()
+}
+
+/// Reads a list of structs with `read_element`.
+fn read_list<T>(
+    protocol: &mut dyn TInputProtocol,
+    read_element: fn(&mut dyn TInputProtocol) -> thrift::Result<T>,
+) -> thrift::Result<Vec<T>> {
+    let list = protocol.read_list_begin()?;
+    let elements = (0..list.size)
+        .map(|_| read_element(protocol))
+        .collect::<thrift::Result<Vec<T>>>()?;
+    protocol.read_list_end()?;
+    Ok(elements)
+}
+
+fn read_statistics(
+    protocol: &mut dyn TInputProtocol,
+    extras: &mut ChunkExtras,
+) -> thrift::Result<()> {
+    read_struct(protocol, &mut |protocol, id, field_type| {
+        match (id, field_type) {
+            (7, TType::Bool) => extras.is_max_value_exact = Some(protocol.read_bool()?),
+            (8, TType::Bool) => extras.is_min_value_exact = Some(protocol.read_bool()?),
+            (1 | 2, _) => {
+                extras.has_legacy_min_max = true;
+                return Ok(false);
+            }
+            (5 | 6, _) => {
+                extras.has_min_max_value = true;
+                return Ok(false);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })
+}
+
+fn read_column_metadata(
+    protocol: &mut dyn TInputProtocol,
+    extras: &mut ChunkExtras,
+) -> thrift::Result<()> {
+    read_struct(protocol, &mut |protocol, id, field_type| {
+        match (id, field_type) {
+            (12, TType::Struct) => read_statistics(protocol, extras)?,
+            (15, TType::I32) => extras.bloom_filter_length = Some(protocol.read_i32()?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })
+}
+
+fn read_column_chunk(protocol: &mut dyn TInputProtocol) -> thrift::Result<ChunkExtras> {
+    let mut extras = ChunkExtras::default();
+    read_struct(protocol, &mut |protocol, id, field_type| {
+        if (id, field_type) != (3, TType::Struct) {
+            return Ok(false);
+        }
+        read_column_metadata(protocol, &mut extras)?;
+        Ok(true)
+    })?;
+    Ok(extras)
+}
+
+fn read_row_group(protocol: &mut dyn TInputProtocol) -> thrift::Result<Vec<ChunkExtras>> {
+    let mut columns = vec![];
+    read_struct(protocol, &mut |protocol, id, field_type| {
+        if (id, field_type) != (1, TType::List) {
+            return Ok(false);
+        }
+        columns = read_list(protocol, read_column_chunk)?;
+        Ok(true)
+    })?;
+    Ok(columns)
+}
+
+/// Reads the [`ChunkExtras`] of every column chunk of a footer, by row
+/// group.
+pub fn decode_chunk_extras(footer: &[u8]) -> thrift::Result<Vec<Vec<ChunkExtras>>> {
+    let mut protocol = TCompactInputProtocol::new(footer);
+    let mut row_groups = vec![];
+    read_struct(&mut protocol, &mut |protocol, id, field_type| {
+        if (id, field_type) != (4, TType::List) {
+            return Ok(false);
+        }
+        row_groups = read_list(protocol, read_row_group)?;
+        Ok(true)
+    })?;
+    Ok(row_groups)
+}
+
+/// Ids of the members of the `LogicalType` union the parquet crate knows,
+/// from STRING to UUID. It panics on any other, like FLOAT16.
+const KNOWN_LOGICAL_TYPES: [i16; 13] = [1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14];
+
+/// Whether a `LogicalType` union is one the parquet crate can decode.
+fn read_logical_type(protocol: &mut dyn TInputProtocol) -> thrift::Result<bool> {
+    let mut known = true;
+    read_struct(protocol, &mut |_, id, _| {
+        known &= KNOWN_LOGICAL_TYPES.contains(&id);
+        Ok(false)
+    })?;
+    Ok(known)
+}
+
+/// Whether the parquet crate can decode the logical type of each schema
+/// element of a footer, or true when it has none.
+fn logical_types_known(footer: &[u8]) -> thrift::Result<Vec<bool>> {
+    let mut protocol = TCompactInputProtocol::new(footer);
+    let mut elements = vec![];
+    read_struct(&mut protocol, &mut |protocol, id, field_type| {
+        if (id, field_type) != (2, TType::List) {
+            return Ok(false);
+        }
+        elements = read_list(protocol, |protocol| {
+            let mut known = true;
+            read_struct(protocol, &mut |protocol, id, field_type| {
+                if (id, field_type) != (10, TType::Struct) {
+                    return Ok(false);
+                }
+                known = read_logical_type(protocol)?;
+                Ok(true)
+            })?;
+            Ok(known)
+        })?;
+        Ok(true)
+    })?;
+    Ok(elements)
+}
+
+/// Called by [`copy`] with each field of a struct and its depth.
+type CopyField<'a> = dyn FnMut(
+        &mut dyn TInputProtocol,
+        &mut dyn TOutputProtocol,
+        &TFieldIdentifier,
+        i8,
+    ) -> thrift::Result<bool>
+    + 'a;
+
+/// Copies a value from `input` to `output`, calling `field` with each
+/// field of a struct. `field` returns false for fields it doesn't write,
+/// which are copied as they are.
+fn copy(
+    input: &mut dyn TInputProtocol,
+    output: &mut dyn TOutputProtocol,
+    value_type: TType,
+    depth: i8,
+    field: &mut CopyField<'_>,
+) -> thrift::Result<()> {
+    if depth <= 0 {
+        return Err(thrift::Error::Protocol(ProtocolError::new(
+            ProtocolErrorKind::DepthLimit,
+            "metadata is nested too deeply",
+        )));
+    }
+    match value_type {
+        TType::Bool => output.write_bool(input.read_bool()?),
+        TType::I08 => output.write_i8(input.read_i8()?),
+        TType::I16 => output.write_i16(input.read_i16()?),
+        TType::I32 => output.write_i32(input.read_i32()?),
+        TType::I64 => output.write_i64(input.read_i64()?),
+        TType::Double => output.write_double(input.read_double()?),
+        TType::String => output.write_bytes(&input.read_bytes()?),
+        TType::Struct => {
+            input.read_struct_begin()?;
+            output.write_struct_begin(&TStructIdentifier::new("struct"))?;
+            loop {
+                let identifier = input.read_field_begin()?;
+                if identifier.field_type == TType::Stop {
+                    break;
+                }
+                if !field(input, output, &identifier, depth - 1)? {
+                    output.write_field_begin(&identifier)?;
+                    copy(input, output, identifier.field_type, depth - 1, field)?;
+                    output.write_field_end()?;
+                }
+                input.read_field_end()?;
+            }
+            output.write_field_stop()?;
+            output.write_struct_end()?;
+            input.read_struct_end()
+        }
+        TType::List => {
+            let list = input.read_list_begin()?;
+            output.write_list_begin(&list)?;
+            for _ in 0..list.size {
+                copy(input, output, list.element_type, depth - 1, field)?;
+            }
+            output.write_list_end()?;
+            input.read_list_end()
+        }
+        TType::Set => {
+            let set = input.read_set_begin()?;
+            output.write_set_begin(&set)?;
+            for _ in 0..set.size {
+                copy(input, output, set.element_type, depth - 1, field)?;
+            }
+            output.write_set_end()?;
+            input.read_set_end()
+        }
+        TType::Map => {
+            let map = input.read_map_begin()?;
+            output.write_map_begin(&map)?;
+            if let (Some(key_type), Some(value_type)) = (map.key_type, map.value_type) {
+                for _ in 0..map.size {
+                    copy(input, output, key_type, depth - 1, field)?;
+                    copy(input, output, value_type, depth - 1, field)?;
+                }
+            }
+            output.write_map_end()?;
+            input.read_map_end()
+        }
+        value_type => Err(thrift::Error::Protocol(ProtocolError::new(
+            ProtocolErrorKind::InvalidData,
+            format!("unexpected type {}", value_type),
+        ))),
+    }
+}
+
+/// Rewrites a footer without the logical types the parquet crate can't
+/// decode, like FLOAT16, so their columns read as their physical type.
+/// `None` when it has none.
+pub fn strip_unknown_logical_types(footer: &[u8]) -> thrift::Result<Option<Vec<u8>>> {
+    let known = logical_types_known(footer)?;
+    if !known.contains(&false) {
+        return Ok(None);
+    }
+    let mut input = TCompactInputProtocol::new(footer);
+    let mut stripped = vec![];
+    let mut output = TCompactOutputProtocol::new(&mut stripped);
+    copy(
+        &mut input,
+        &mut output,
+        TType::Struct,
+        MAX_SKIP_DEPTH,
+        &mut |input, output, identifier, depth| {
+            // only schema elements, in the list in field 2, have logical types
+            let schema = depth == MAX_SKIP_DEPTH - 1
+                && (identifier.id, identifier.field_type) == (Some(2), TType::List);
+            if !schema {
+                return Ok(false);
+            }
+            output.write_field_begin(identifier)?;
+            let list = input.read_list_begin()?;
+            output.write_list_begin(&list)?;
+            for known in &known {
+                copy(
+                    input,
+                    output,
+                    TType::Struct,
+                    depth,
+                    &mut |input, _, identifier, field_depth| {
+                        let logical_type = field_depth == depth - 1
+                            && (identifier.id, identifier.field_type) == (Some(10), TType::Struct);
+                        if !logical_type || *known {
+                            return Ok(false);
+                        }
+                        skip(input, TType::Struct, MAX_SKIP_DEPTH)?;
+                        Ok(true)
+                    },
+                )?;
+            }
+            output.write_list_end()?;
+            input.read_list_end()?;
+            output.write_field_end()?;
+            Ok(true)
+        },
+    )?;
+    output.flush()?;
+    drop(output);
+    Ok(Some(stripped))
+}
+
+/// A parquet file read through a copy of its footer without the logical
+/// types the parquet crate can't decode, when it has any. The pages before
+/// the footer are read from the file as they are.
+pub struct PatchedChunks<R> {
+    chunks: R,
+    /// Where the file's own footer starts, and the one read in its place
+    patch: Option<(u64, Bytes)>,
+}
+
+impl<R: ChunkReader> PatchedChunks<R> {
+    pub fn new(chunks: R) -> PatchedChunks<R> {
+        // a footer that can't be read is left for the parquet crate to report
+        let patch = read_footer(&chunks, "").ok().and_then(|footer| {
+            let mut patched = strip_unknown_logical_types(&footer).ok()??;
+            let start = chunks.len() - (footer.len() + FOOTER_SIZE) as u64;
+            patched.extend((patched.len() as u32).to_le_bytes());
+            patched.extend(b"PAR1");
+            Some((start, Bytes::from(patched)))
+        });
+        PatchedChunks { chunks, patch }
+    }
+}
+
+impl<R: ChunkReader> Length for PatchedChunks<R> {
+    fn len(&self) -> u64 {
+        match &self.patch {
+            Some((start, footer)) => start + footer.len() as u64,
+            None => self.chunks.len(),
+        }
+    }
+}
+
+impl<R: ChunkReader> ChunkReader for PatchedChunks<R>
+where
+    R::T: 'static,
+{
+    type T = Box<dyn Read + Send>;
+
+    fn get_read(&self, start: u64, length: usize) -> Result<Self::T> {
+        match &self.patch {
+            Some((footer_start, _)) if start + length as u64 > *footer_start => {
+                Ok(Box::new(self.get_bytes(start, length)?.reader()))
+            }
+            _ => Ok(Box::new(self.chunks.get_read(start, length)?)),
+        }
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> Result<Bytes> {
+        let end = start + length as u64;
+        let (footer_start, footer) = match &self.patch {
+            Some((footer_start, footer)) if end > *footer_start => (*footer_start, footer),
+            _ => return self.chunks.get_bytes(start, length),
+        };
+        if end > self.len() {
+            return Err(ParquetError::EOF(format!(
+                "expected to read {} bytes at {}, but the file is only {}",
+                length,
+                start,
+                self.len()
+            )));
+        }
+        let mut bytes = Vec::with_capacity(length);
+        if start < footer_start {
+            bytes.extend(
+                self.chunks
+                    .get_bytes(start, (footer_start - start) as usize)?,
+            );
+        }
+        let from = start.saturating_sub(footer_start) as usize;
+        bytes.extend(&footer[from..(end - footer_start) as usize]);
+        Ok(bytes.into())
+    }
+}
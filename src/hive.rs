@@ -0,0 +1,152 @@
+use std::path::{Path, PathBuf};
+
+use crate::pruning::Scalar;
+
+/// Hive's name for a partition whose value was NULL.
+static DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// A column that comes from `key=value` directories in file paths, like
+/// `date` and `region` in `events/date=2022-10-25/region=us/part-0.parquet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionColumn {
+    pub name: String,
+    /// INTEGER, REAL or TEXT, whichever fits every value
+    pub sql_type: &'static str,
+}
+
+/// Decodes the %XX escapes hive uses for special characters in values.
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The directory a `filename=` argument's files are found under: a
+/// directory itself, the directories before the first glob character of a
+/// pattern, or a file's own directory. Only directories below it can be
+/// partitions.
+pub fn partition_root(filename: &str) -> PathBuf {
+    let path = Path::new(filename);
+    if path.is_dir() {
+        return path.to_path_buf();
+    }
+    let mut root = PathBuf::new();
+    for component in path.components() {
+        if component
+            .as_os_str()
+            .to_string_lossy()
+            .contains(['*', '?', '['])
+        {
+            return root;
+        }
+        root.push(component);
+    }
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// The `key=value` directories in a file's path below `root`, outermost
+/// first.
+fn path_partitions(path: &str, root: &Path) -> Vec<(String, String)> {
+    let below = match Path::new(path)
+        .parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+    {
+        Some(below) => below,
+        None => return vec![],
+    };
+    below
+        .iter()
+        .filter_map(|segment| {
+            let (key, value) = segment.to_str()?.split_once('=')?;
+            (!key.is_empty()).then(|| (unescape(key), unescape(value)))
+        })
+        .collect()
+}
+
+/// Finds the partition columns of a set of files, each with the root its
+/// `filename=` argument gave, leaving out names in `exclude`, and each
+/// file's values for them. Files without a partition get NULL for it.
+pub fn discover_partitions(
+    paths: &[String],
+    roots: &[PathBuf],
+    exclude: &[&str],
+) -> (Vec<PartitionColumn>, Vec<Vec<Option<Scalar>>>) {
+    let partitions: Vec<Vec<(String, String)>> = paths
+        .iter()
+        .zip(roots)
+        .map(|(path, root)| path_partitions(path, root))
+        .collect();
+    let mut names: Vec<String> = vec![];
+    for (key, _) in partitions.iter().flatten() {
+        if !names.contains(key) && !exclude.contains(&key.as_str()) {
+            names.push(key.clone());
+        }
+    }
+
+    // raw values, by column and then by file
+    let raw: Vec<Vec<Option<&str>>> = names
+        .iter()
+        .map(|name| {
+            partitions
+                .iter()
+                .map(|file| {
+                    file.iter()
+                        .find(|(key, _)| key == name)
+                        .map(|(_, value)| value.as_str())
+                        .filter(|value| *value != DEFAULT_PARTITION)
+                })
+                .collect()
+        })
+        .collect();
+
+    let columns: Vec<PartitionColumn> = names
+        .into_iter()
+        .zip(&raw)
+        .map(|(name, values)| {
+            let values = || values.iter().flatten();
+            let sql_type = if values().all(|value| value.parse::<i64>().is_ok()) {
+                "INTEGER"
+            } else if values().all(|value| value.parse::<f64>().is_ok()) {
+                "REAL"
+            } else {
+                "TEXT"
+            };
+            PartitionColumn { name, sql_type }
+        })
+        .collect();
+
+    let values = (0..paths.len())
+        .map(|file| {
+            columns
+                .iter()
+                .zip(&raw)
+                .map(|(column, values)| {
+                    let value = values[file]?;
+                    Some(match column.sql_type {
+                        "INTEGER" => Scalar::Integer(value.parse::<i64>().ok()?.into()),
+                        "REAL" => Scalar::Real(value.parse().ok()?),
+                        _ => Scalar::Text(value.to_owned()),
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    (columns, values)
+}
@@ -0,0 +1,224 @@
+use arrow::{
+    datatypes::{DataType, Field, Schema},
+    ipc::{convert::fb_to_schema, root_as_message},
+};
+use parquet::file::metadata::KeyValue;
+use serde_json::{json, Map, Value};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
+};
+
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::errors::catch_panic;
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::trace::Trace;
+
+/// Key the Arrow writers store their schema under, as a base64 encoded
+/// IPC message.
+const ARROW_SCHEMA_KEY: &str = "ARROW:schema";
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      source hidden,
+      key text,
+      value
+    )";
+enum Columns {
+    Source,
+    Key,
+    Value,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Source),
+        1 => Some(Columns::Key),
+        2 => Some(Columns::Value),
+        _ => None,
+    }
+}
+
+/// Describes an Arrow field, with the fields inside it for nested types.
+fn arrow_field_json(field: &Field) -> Value {
+    let (data_type, children) = match field.data_type() {
+        DataType::List(child) => ("List".to_owned(), vec![child.as_ref()]),
+        DataType::LargeList(child) => ("LargeList".to_owned(), vec![child.as_ref()]),
+        DataType::FixedSizeList(child, size) => {
+            (format!("FixedSizeList({})", size), vec![child.as_ref()])
+        }
+        DataType::Map(child, _) => ("Map".to_owned(), vec![child.as_ref()]),
+        DataType::Struct(children) => ("Struct".to_owned(), children.iter().collect()),
+        DataType::Timestamp(unit, Some(timezone)) => {
+            (format!("Timestamp({:?}, {})", unit, timezone), vec![])
+        }
+        DataType::Timestamp(unit, None) => (format!("Timestamp({:?})", unit), vec![]),
+        DataType::Union(children, _, mode) => {
+            (format!("Union({:?})", mode), children.iter().collect())
+        }
+        data_type => (format!("{:?}", data_type), vec![]),
+    };
+    let mut object = Map::new();
+    object.insert("name".to_owned(), json!(field.name()));
+    object.insert("type".to_owned(), json!(data_type));
+    object.insert("nullable".to_owned(), json!(field.is_nullable()));
+    if !children.is_empty() {
+        let children = children.into_iter().map(arrow_field_json).collect();
+        object.insert("children".to_owned(), Value::Array(children));
+    }
+    if let Some(metadata) = field.metadata().filter(|metadata| !metadata.is_empty()) {
+        object.insert("metadata".to_owned(), json!(metadata));
+    }
+    Value::Object(object)
+}
+
+/// Decodes the Arrow schema the Arrow writers store in the key/value
+/// metadata, or `None` when it isn't one.
+fn decode_arrow_schema(encoded: &str) -> Option<Value> {
+    let bytes = base64::decode(encoded).ok()?;
+    // newer writers prefix the message with a continuation marker and length
+    let message = match bytes.get(0..4) {
+        Some([0xff, 0xff, 0xff, 0xff]) => bytes.get(8..)?,
+        _ => &bytes,
+    };
+    let message = root_as_message(message).ok()?;
+    let schema = message.header_as_schema()?;
+    // the conversion panics on types it doesn't know
+    let schema: Schema = catch_panic(|| fb_to_schema(schema)).ok()?;
+    let mut object = Map::new();
+    let fields = schema.fields().iter().map(arrow_field_json).collect();
+    object.insert("fields".to_owned(), Value::Array(fields));
+    if !schema.metadata().is_empty() {
+        object.insert("metadata".to_owned(), json!(schema.metadata()));
+    }
+    Some(Value::Object(object))
+}
+
+/// A key/value metadata value as JSON: the decoded schema for
+/// `ARROW:schema`, and the text as it is for anything else.
+fn kv_value_json(entry: &KeyValue) -> Value {
+    match &entry.value {
+        Some(value) if entry.key == ARROW_SCHEMA_KEY => {
+            decode_arrow_schema(value).unwrap_or_else(|| json!(value))
+        }
+        Some(value) => json!(value),
+        None => Value::Null,
+    }
+}
+
+/// The key/value metadata of a file as one JSON object, for
+/// `parquet_metadata`.
+pub fn kv_metadata_json(entries: &[KeyValue]) -> Value {
+    Value::Object(
+        entries
+            .iter()
+            .map(|entry| (entry.key.clone(), kv_value_json(entry)))
+            .collect(),
+    )
+}
+
+#[repr(C)]
+pub struct KvMetadataTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for KvMetadataTable {
+    type Aux = Rc<Trace>;
+    type Cursor = KvMetadataCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, KvMetadataTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), KvMetadataTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(info, &[Columns::Source as c_int], 1)
+    }
+
+    fn open(&mut self) -> Result<KvMetadataCursor> {
+        Ok(KvMetadataCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct KvMetadataCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    entries: Vec<KeyValue>,
+    entry_idx: usize,
+}
+
+impl KvMetadataCursor {
+    fn new(trace: Rc<Trace>) -> KvMetadataCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        KvMetadataCursor {
+            base,
+            trace,
+            entries: vec![],
+            entry_idx: 0,
+        }
+    }
+}
+
+impl VTabCursor for KvMetadataCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        let reader = source.open(&self.trace)?;
+        self.entries = reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .cloned()
+            .unwrap_or_default();
+        self.entry_idx = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.entry_idx += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.entry_idx >= self.entries.len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let entry = match self.entries.get(self.entry_idx) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        match column(i) {
+            Some(Columns::Source) => (),
+            Some(Columns::Key) => api::result_text(context, &entry.key)?,
+            Some(Columns::Value) => match kv_value_json(entry) {
+                Value::Null => api::result_null(context),
+                Value::String(value) => api::result_text(context, value)?,
+                value => api::result_json(context, value)?,
+            },
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.entry_idx as i64)
+    }
+}
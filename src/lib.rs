@@ -1,18 +1,53 @@
+mod bloom;
 mod column_chunks;
+mod column_index;
+mod column_values;
+mod columns;
+mod each;
+mod errors;
+mod footer;
+mod hive;
+mod kv_metadata;
+mod logical;
 mod meta;
 mod metadata;
+mod offset_index;
+mod options;
+mod page_index;
+mod pages;
 mod parquet;
+mod pruning;
+mod raw;
+mod row_groups;
+mod schema;
+mod source;
+mod stats;
+mod timestamps;
+mod trace;
 
 use sqlite_loadable::prelude::*;
 use sqlite_loadable::{
-    define_scalar_function, define_table_function, define_virtual_table, FunctionFlags, Result,
+    define_scalar_function, define_scalar_function_with_aux, define_table_function,
+    define_virtual_table, FunctionFlags, Result,
 };
 
+use std::rc::Rc;
+
 use crate::{
+    bloom::parquet_bloom_check,
     column_chunks::ColumnChunksTable,
+    column_index::ColumnIndexTable,
+    column_values::ColumnValuesTable,
+    columns::ColumnsTable,
+    each::EachTable,
+    kv_metadata::KvMetadataTable,
     meta::{parquet_debug, parquet_version},
     metadata::MetadataTable,
+    offset_index::OffsetIndexTable,
+    pages::PagesTable,
     parquet::ParquetTable,
+    row_groups::RowGroupsTable,
+    trace::{parquet_trace, Trace, TraceLogTable},
 };
 
 #[sqlite_entrypoint]
@@ -32,9 +67,37 @@ pub fn sqlite3_parquet_init(db: *mut sqlite3) -> Result<()> {
         FunctionFlags::DETERMINISTIC,
     )?;
 
-    define_virtual_table::<ParquetTable>(db, "parquet", None)?;
-    define_table_function::<MetadataTable>(db, "parquet_metadata", None)?;
-    define_table_function::<ColumnChunksTable>(db, "parquet_column_chunks", None)?;
+    let trace = Rc::new(Trace::from_env());
+    define_scalar_function_with_aux(
+        db,
+        "parquet_trace",
+        -1,
+        parquet_trace,
+        FunctionFlags::empty(),
+        trace.clone(),
+    )?;
+
+    define_scalar_function_with_aux(
+        db,
+        "parquet_bloom_check",
+        3,
+        parquet_bloom_check,
+        FunctionFlags::empty(),
+        trace.clone(),
+    )?;
+
+    define_virtual_table::<ParquetTable>(db, "parquet", Some(trace.clone()))?;
+    define_table_function::<MetadataTable>(db, "parquet_metadata", Some(trace.clone()))?;
+    define_table_function::<KvMetadataTable>(db, "parquet_kv_metadata", Some(trace.clone()))?;
+    define_table_function::<RowGroupsTable>(db, "parquet_row_groups", Some(trace.clone()))?;
+    define_table_function::<ColumnsTable>(db, "parquet_columns", Some(trace.clone()))?;
+    define_table_function::<EachTable>(db, "parquet_each", Some(trace.clone()))?;
+    define_table_function::<ColumnValuesTable>(db, "parquet_column_values", Some(trace.clone()))?;
+    define_table_function::<ColumnChunksTable>(db, "parquet_column_chunks", Some(trace.clone()))?;
+    define_table_function::<PagesTable>(db, "parquet_pages", Some(trace.clone()))?;
+    define_table_function::<ColumnIndexTable>(db, "parquet_column_index", Some(trace.clone()))?;
+    define_table_function::<OffsetIndexTable>(db, "parquet_offset_index", Some(trace.clone()))?;
+    define_table_function::<TraceLogTable>(db, "parquet_trace_log", Some(trace))?;
 
     Ok(())
 }
@@ -0,0 +1,193 @@
+use chrono::NaiveDateTime;
+use serde_json::{json, Map, Value};
+
+/// Formats a 16 byte UUID like `f81d4fae-7dec-11d0-a765-00a0c91e6bf6`.
+pub fn format_uuid(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 16 {
+        return None;
+    }
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+/// Formats an INTERVAL, three little-endian u32s of months, days and
+/// milliseconds, as an ISO-8601 duration like `P1Y2M3DT4H5M6.789S`.
+pub fn format_interval(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 12 {
+        return None;
+    }
+    let part = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let (months, days, millis) = (part(0), part(4), part(8));
+    let mut duration = String::from("P");
+    for (value, unit) in [(months / 12, 'Y'), (months % 12, 'M'), (days, 'D')] {
+        if value > 0 {
+            duration.push_str(&format!("{}{}", value, unit));
+        }
+    }
+    if millis > 0 {
+        duration.push('T');
+        let seconds = millis / 1000;
+        for (value, unit) in [(seconds / 3600, 'H'), (seconds / 60 % 60, 'M')] {
+            if value > 0 {
+                duration.push_str(&format!("{}{}", value, unit));
+            }
+        }
+        match (seconds % 60, millis % 1000) {
+            (0, 0) => (),
+            (seconds, 0) => duration.push_str(&format!("{}S", seconds)),
+            (seconds, millis) => {
+                let fraction = format!("{:03}", millis);
+                duration.push_str(&format!("{}.{}S", seconds, fraction.trim_end_matches('0')));
+            }
+        }
+    }
+    if duration == "P" {
+        duration.push_str("T0S");
+    }
+    Some(duration)
+}
+
+/// Reads a BSON document from the front of `bytes`.
+struct Bson<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Bson<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err("BSON value is cut off".to_owned());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn cstring(&mut self) -> Result<String, String> {
+        let end = self
+            .bytes
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| "BSON name isn't terminated".to_owned())?;
+        let text = String::from_utf8_lossy(self.take(end)?).into_owned();
+        self.take(1)?;
+        Ok(text)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = usize::try_from(self.i32()?).map_err(|_| "negative BSON string length")?;
+        let bytes = self.take(len)?;
+        Ok(String::from_utf8_lossy(&bytes[..len.saturating_sub(1)]).into_owned())
+    }
+
+    /// A document's elements, as `(name, value)` pairs.
+    fn document(&mut self) -> Result<Vec<(String, Value)>, String> {
+        let len = usize::try_from(self.i32()?).map_err(|_| "negative BSON document length")?;
+        let mut inner = Bson {
+            bytes: self.take(len.saturating_sub(4))?,
+        };
+        let mut elements = vec![];
+        loop {
+            let kind = inner.take(1)?[0];
+            if kind == 0 {
+                return Ok(elements);
+            }
+            let name = inner.cstring()?;
+            let value = inner.value(kind)?;
+            elements.push((name, value));
+        }
+    }
+
+    /// A value of the given element type, as relaxed extended JSON.
+    fn value(&mut self, kind: u8) -> Result<Value, String> {
+        Ok(match kind {
+            0x01 => {
+                let value = f64::from_bits(self.u64()?);
+                if value.is_finite() {
+                    json!(value)
+                } else {
+                    json!({ "$numberDouble": value.to_string() })
+                }
+            }
+            0x02 => Value::String(self.string()?),
+            0x03 => Value::Object(self.document()?.into_iter().collect::<Map<_, _>>()),
+            0x04 => Value::Array(self.document()?.into_iter().map(|(_, v)| v).collect()),
+            0x05 => {
+                let len =
+                    usize::try_from(self.i32()?).map_err(|_| "negative BSON binary length")?;
+                let subtype = self.take(1)?[0];
+                json!({ "$binary": {
+                    "base64": base64::encode(self.take(len)?),
+                    "subType": format!("{:02x}", subtype),
+                }})
+            }
+            0x06 | 0x0A => Value::Null,
+            0x07 => {
+                let oid: String = self
+                    .take(12)?
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                json!({ "$oid": oid })
+            }
+            0x08 => json!(self.take(1)?[0] != 0),
+            0x09 => {
+                let millis = self.u64()? as i64;
+                match NaiveDateTime::from_timestamp_opt(
+                    millis.div_euclid(1000),
+                    (millis.rem_euclid(1000) * 1_000_000) as u32,
+                ) {
+                    Some(date)
+                        if (1970..=9999).contains(
+                            &date.format("%Y").to_string().parse::<i32>().unwrap_or(0),
+                        ) =>
+                    {
+                        json!({ "$date": date.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string() })
+                    }
+                    _ => json!({ "$date": { "$numberLong": millis.to_string() } }),
+                }
+            }
+            0x0B => {
+                let pattern = self.cstring()?;
+                let options = self.cstring()?;
+                json!({ "$regularExpression": { "pattern": pattern, "options": options } })
+            }
+            0x0D => json!({ "$code": self.string()? }),
+            0x0E => json!({ "$symbol": self.string()? }),
+            0x10 => json!(self.i32()?),
+            0x11 => {
+                let value = self.u64()?;
+                json!({ "$timestamp": { "t": value >> 32, "i": value & 0xffff_ffff } })
+            }
+            0x12 => json!(self.u64()? as i64),
+            0x7F => json!({ "$maxKey": 1 }),
+            0xFF => json!({ "$minKey": 1 }),
+            kind => return Err(format!("unsupported BSON type 0x{:02x}", kind)),
+        })
+    }
+}
+
+/// Converts a BSON document to relaxed extended JSON, like MongoDB's tools
+/// print it.
+pub fn bson_to_json(bytes: &[u8]) -> Result<Value, String> {
+    let mut bson = Bson { bytes };
+    let document = bson.document()?;
+    Ok(Value::Object(document.into_iter().collect()))
+}
@@ -5,14 +5,14 @@ pub fn parquet_version(
     context: *mut sqlite3_context,
     _values: &[*mut sqlite3_value],
 ) -> Result<()> {
-    api::result_text(context, &format!("v{}", env!("CARGO_PKG_VERSION")))?;
+    api::result_text(context, format!("v{}", env!("CARGO_PKG_VERSION")))?;
     Ok(())
 }
 
 pub fn parquet_debug(context: *mut sqlite3_context, _values: &[*mut sqlite3_value]) -> Result<()> {
     api::result_text(
         context,
-        &format!(
+        format!(
             "Version: v{}
 Source: {}
 ",
@@ -1,17 +1,16 @@
-use parquet::{
-    file::{
-        metadata::ParquetMetaData, reader::FileReader, serialized_reader::SerializedFileReader,
-    },
-    schema::printer,
-};
+use parquet::{file::metadata::ParquetMetaData, schema::printer};
 use sqlite_loadable::prelude::*;
 use sqlite_loadable::{
     api,
-    table::{ConstraintOperator, IndexInfo, VTab, VTabArguments, VTabCursor},
-    BestIndexError, Error, Result,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
 };
 
-use std::{fs::File, mem, os::raw::c_int};
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::kv_metadata::kv_metadata_json;
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::trace::Trace;
 
 static CREATE_SQL: &str = "CREATE TABLE x(
     source hidden, 
@@ -20,8 +19,10 @@ static CREATE_SQL: &str = "CREATE TABLE x(
     schema text,
     num_rows integer, 
     num_columns integer,
-    num_row_groups integer
+    num_row_groups integer,
+    key_value_metadata
   )";
+#[allow(clippy::enum_variant_names)]
 enum Columns {
     Source,
     Version,
@@ -30,6 +31,7 @@ enum Columns {
     NumRows,
     NumColumns,
     NumRowGroups,
+    KeyValueMetadata,
 }
 fn column(index: i32) -> Option<Columns> {
     match index {
@@ -40,6 +42,7 @@ fn column(index: i32) -> Option<Columns> {
         4 => Some(Columns::NumRows),
         5 => Some(Columns::NumColumns),
         6 => Some(Columns::NumRowGroups),
+        7 => Some(Columns::KeyValueMetadata),
         _ => None,
     }
 }
@@ -48,19 +51,21 @@ fn column(index: i32) -> Option<Columns> {
 pub struct MetadataTable {
     /// must be first
     base: sqlite3_vtab,
+    trace: Rc<Trace>,
 }
 
 impl<'vtab> VTab<'vtab> for MetadataTable {
-    type Aux = ();
+    type Aux = Rc<Trace>;
     type Cursor = MetadataCursor;
 
     fn connect(
         _db: *mut sqlite3,
-        _aux: Option<&Self::Aux>,
+        aux: Option<&Self::Aux>,
         _args: VTabArguments,
     ) -> Result<(String, MetadataTable)> {
         let base: sqlite3_vtab = unsafe { mem::zeroed() };
-        let vtab = MetadataTable { base };
+        let trace = trace_aux(aux)?;
+        let vtab = MetadataTable { base, trace };
         // TODO db.config(VTabConfig::Innocuous)?;
         Ok((CREATE_SQL.to_owned(), vtab))
     }
@@ -68,35 +73,12 @@ impl<'vtab> VTab<'vtab> for MetadataTable {
         Ok(())
     }
 
-    fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
-        let mut has_source = false;
-        for mut constraint in info.constraints() {
-            //println!("{} {}", constraint.icolumn(), constraint.usable());
-            match column(constraint.column_idx()) {
-                Some(Columns::Source) => {
-                    if constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ) {
-                        constraint.set_omit(true);
-                        constraint.set_argv_index(1);
-                        has_source = true;
-                    } else {
-                        return Err(BestIndexError::Constraint);
-                    }
-                }
-                _ => todo!(),
-            }
-        }
-        if !has_source {
-            return Err(BestIndexError::Error);
-        }
-        info.set_estimated_cost(100000.0);
-        info.set_estimated_rows(100000);
-        info.set_idxnum(1);
-
-        Ok(())
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(info, &[Columns::Source as c_int], 1)
     }
 
     fn open(&mut self) -> Result<MetadataCursor> {
-        Ok(MetadataCursor::new())
+        Ok(MetadataCursor::new(self.trace.clone()))
     }
 }
 
@@ -104,14 +86,16 @@ impl<'vtab> VTab<'vtab> for MetadataTable {
 pub struct MetadataCursor {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
     metadata: Option<ParquetMetaData>,
     done: bool,
 }
 impl MetadataCursor {
-    fn new() -> MetadataCursor {
+    fn new(trace: Rc<Trace>) -> MetadataCursor {
         let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
         MetadataCursor {
             base,
+            trace,
             metadata: None,
             done: false,
         }
@@ -125,10 +109,8 @@ impl VTabCursor for MetadataCursor {
         _idx_str: Option<&str>,
         values: &[*mut sqlite3_value],
     ) -> Result<()> {
-        let path = api::value_text(values.get(0).unwrap())?;
-        println!("{path}");
-        let file = File::open(&path).unwrap();
-        let reader = SerializedFileReader::new(file).unwrap();
+        let source = Source::from_value(values.first())?;
+        let reader = source.open(&self.trace)?;
         let metadata = reader.metadata();
         self.metadata = Some(metadata.to_owned());
         self.done = false;
@@ -145,7 +127,10 @@ impl VTabCursor for MetadataCursor {
     }
 
     fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
-        let metadata = self.metadata.as_ref().unwrap();
+        let metadata = match self.metadata.as_ref() {
+            Some(metadata) => metadata,
+            None => return Ok(()),
+        };
 
         match column(i) {
             Some(Columns::Source) => (),
@@ -161,7 +146,7 @@ impl VTabCursor for MetadataCursor {
                 let schema = metadata.file_metadata().schema();
                 let mut buf = Vec::new();
                 printer::print_schema(&mut buf, schema);
-                api::result_text(context, String::from_utf8(buf).unwrap().as_str())?;
+                api::result_text(context, String::from_utf8_lossy(&buf).as_ref())?;
             }
             Some(Columns::NumRows) => {
                 api::result_int64(context, metadata.file_metadata().num_rows());
@@ -169,19 +154,19 @@ impl VTabCursor for MetadataCursor {
             Some(Columns::NumColumns) => {
                 api::result_int64(
                     context,
-                    metadata
-                        .file_metadata()
-                        .schema_descr()
-                        .num_columns()
-                        .try_into()
-                        .unwrap(),
+                    metadata.file_metadata().schema_descr().num_columns() as i64,
                 );
             }
             Some(Columns::NumRowGroups) => {
-                api::result_int64(context, metadata.num_row_groups().try_into().unwrap());
+                api::result_int64(context, metadata.num_row_groups() as i64);
+            }
+            Some(Columns::KeyValueMetadata) => {
+                if let Some(entries) = metadata.file_metadata().key_value_metadata() {
+                    api::result_json(context, kv_metadata_json(entries))?;
+                }
             }
 
-            None => todo!(),
+            None => (),
         }
         Ok(())
     }
@@ -0,0 +1,206 @@
+use parquet::{
+    file::{metadata::ParquetMetaData, reader::ChunkReader},
+    format::PageLocation,
+    schema::types::SchemaDescPtr,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
+};
+
+use std::{mem, ops::Range, os::raw::c_int, rc::Rc};
+
+use crate::errors::parquet_error;
+use crate::page_index::{page_rows, read_offset_index};
+use crate::source::{open_path, source_best_index, trace_aux, Source};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      source hidden,
+      row_group integer,
+      column text,
+      page integer,
+      offset integer,
+      compressed_page_size integer,
+      first_row_index integer,
+      num_rows integer
+    )";
+enum Columns {
+    Source,
+    RowGroup,
+    Column,
+    Page,
+    Offset,
+    CompressedPageSize,
+    FirstRowIndex,
+    NumRows,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Source),
+        1 => Some(Columns::RowGroup),
+        2 => Some(Columns::Column),
+        3 => Some(Columns::Page),
+        4 => Some(Columns::Offset),
+        5 => Some(Columns::CompressedPageSize),
+        6 => Some(Columns::FirstRowIndex),
+        7 => Some(Columns::NumRows),
+        _ => None,
+    }
+}
+
+/// A page's location in the offset index of its column chunk.
+struct LocationRow {
+    row_group: usize,
+    leaf: usize,
+    /// Index of the page in its column chunk's data pages, from 0
+    page: usize,
+    location: PageLocation,
+    /// The page's rows, up to where the next page starts
+    rows: Range<i64>,
+}
+
+/// Reads the offset index of every column chunk that has one.
+fn read_offset_indexes<R: ChunkReader>(
+    chunks: &R,
+    metadata: &ParquetMetaData,
+    name: &str,
+) -> Result<Vec<LocationRow>> {
+    let mut rows = vec![];
+    for (row_group, row_group_metadata) in metadata.row_groups().iter().enumerate() {
+        for (leaf, chunk) in row_group_metadata.columns().iter().enumerate() {
+            let locations = match read_offset_index(chunks, chunk) {
+                Ok(Some(locations)) => locations,
+                Ok(None) => continue,
+                Err(err) => return Err(parquet_error(name, err)),
+            };
+            let page_rows = page_rows(&locations, row_group_metadata.num_rows());
+            rows.extend(locations.iter().zip(page_rows).enumerate().map(
+                |(page, (location, page_rows))| LocationRow {
+                    row_group,
+                    leaf,
+                    page,
+                    location: location.clone(),
+                    rows: page_rows,
+                },
+            ));
+        }
+    }
+    Ok(rows)
+}
+
+#[repr(C)]
+pub struct OffsetIndexTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for OffsetIndexTable {
+    type Aux = Rc<Trace>;
+    type Cursor = OffsetIndexCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, OffsetIndexTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), OffsetIndexTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(info, &[Columns::Source as c_int], 1)
+    }
+
+    fn open(&mut self) -> Result<OffsetIndexCursor> {
+        Ok(OffsetIndexCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct OffsetIndexCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    schema_descr: Option<SchemaDescPtr>,
+    rows: Vec<LocationRow>,
+    row_idx: usize,
+}
+
+impl OffsetIndexCursor {
+    fn new(trace: Rc<Trace>) -> OffsetIndexCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        OffsetIndexCursor {
+            base,
+            trace,
+            schema_descr: None,
+            rows: vec![],
+            row_idx: 0,
+        }
+    }
+}
+
+impl VTabCursor for OffsetIndexCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        let reader = source.open(&self.trace)?;
+        let metadata = reader.metadata();
+        self.rows = match &source {
+            Source::Path(path) => read_offset_indexes(&open_path(path)?, metadata, path)?,
+            Source::Blob(blob) => read_offset_indexes(blob, metadata, source.name())?,
+        };
+        self.schema_descr = Some(metadata.file_metadata().schema_descr_ptr());
+        self.row_idx = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.row_idx += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row_idx >= self.rows.len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let (row, schema_descr) = match (self.rows.get(self.row_idx), &self.schema_descr) {
+            (Some(row), Some(schema_descr)) => (row, schema_descr),
+            _ => return Ok(()),
+        };
+        match column(i) {
+            Some(Columns::Source) => (),
+            Some(Columns::RowGroup) => api::result_int64(context, row.row_group as i64),
+            Some(Columns::Column) => {
+                api::result_text(context, schema_descr.column(row.leaf).path().string())?
+            }
+            Some(Columns::Page) => api::result_int64(context, row.page as i64),
+            Some(Columns::Offset) => api::result_int64(context, row.location.offset),
+            Some(Columns::CompressedPageSize) => {
+                api::result_int(context, row.location.compressed_page_size)
+            }
+            Some(Columns::FirstRowIndex) => {
+                api::result_int64(context, row.location.first_row_index)
+            }
+            Some(Columns::NumRows) => api::result_int64(context, row.rows.end - row.rows.start),
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.row_idx as i64)
+    }
+}
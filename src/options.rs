@@ -0,0 +1,137 @@
+use sqlite_loadable::{Error, Result};
+
+use crate::pruning::Scalar;
+
+/// How DECIMAL values are returned, set with `decimal=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecimalMode {
+    /// The exact value as text, ex `'123.45'`
+    #[default]
+    Text,
+    /// The closest REAL, which can lose digits past 15 or so
+    Real,
+    /// The unscaled INTEGER, ex `12345` for `123.45` with a scale of 2
+    IntScaled,
+}
+
+/// How timestamps are returned, set with `timestamps=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampMode {
+    /// `YYYY-MM-DD HH:MM:SS.fff` text, with every digit of the column's
+    /// unit, and a `Z` for UTC-adjusted values
+    #[default]
+    Iso8601,
+    /// INTEGER seconds since 1970-01-01, rounded down
+    UnixEpoch,
+    /// INTEGER milliseconds since 1970-01-01, rounded down
+    UnixEpochMs,
+    /// REAL days since noon in Greenwich on November 24, 4714 B.C.
+    JulianDay,
+}
+
+/// What UINT64 values past the largest INTEGER become, set with
+/// `uint64_overflow=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Uint64Overflow {
+    /// The query fails
+    Error,
+    /// The closest REAL, which can lose the last few digits
+    Real,
+    /// The exact value as text, ex `'18446744073709551615'`
+    #[default]
+    Text,
+    /// NULL
+    Null,
+}
+
+impl Uint64Overflow {
+    pub fn parse(key: &str, value: &str) -> Result<Uint64Overflow> {
+        match value.to_ascii_lowercase().as_str() {
+            "error" => Ok(Uint64Overflow::Error),
+            "real" => Ok(Uint64Overflow::Real),
+            "text" => Ok(Uint64Overflow::Text),
+            "null" => Ok(Uint64Overflow::Null),
+            _ => Err(invalid_value(key, value, "error, real, text or null")),
+        }
+    }
+
+    /// The value a UINT64 reads as, `None` for NULL, or the error message
+    /// when it's too large and this is `Error`.
+    pub fn scalar(self, value: u64) -> std::result::Result<Option<Scalar>, String> {
+        if let Ok(value) = i64::try_from(value) {
+            return Ok(Some(Scalar::Integer(value.into())));
+        }
+        match self {
+            Uint64Overflow::Error => Err(format!("value too large for an integer: {}", value)),
+            Uint64Overflow::Real => Ok(Some(Scalar::Real(value as f64))),
+            Uint64Overflow::Text => Ok(Some(Scalar::Text(value.to_string()))),
+            Uint64Overflow::Null => Ok(None),
+        }
+    }
+}
+
+/// How struct columns are read, set with `nested=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NestedMode {
+    /// One JSON column per struct
+    #[default]
+    Json,
+    /// One column per struct leaf, named by its path like `"address.city"`.
+    /// Lists and maps stay JSON.
+    Flatten,
+}
+
+/// Options of the `parquet` module that change how values are read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOptions {
+    pub decimal: DecimalMode,
+    pub timestamps: TimestampMode,
+    pub uint64_overflow: Uint64Overflow,
+    pub nested: NestedMode,
+}
+
+impl ReadOptions {
+    /// Applies a `key=value` argument, returning false when `key` isn't
+    /// one of these options.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "decimal" => {
+                self.decimal = match value.to_ascii_lowercase().as_str() {
+                    "text" => DecimalMode::Text,
+                    "real" => DecimalMode::Real,
+                    "int_scaled" => DecimalMode::IntScaled,
+                    _ => return Err(invalid_value(key, value, "text, real or int_scaled")),
+                }
+            }
+            "timestamps" => {
+                self.timestamps = match value.to_ascii_lowercase().as_str() {
+                    "iso8601" => TimestampMode::Iso8601,
+                    "unixepoch" => TimestampMode::UnixEpoch,
+                    "unixepoch_ms" => TimestampMode::UnixEpochMs,
+                    "julianday" => TimestampMode::JulianDay,
+                    _ => {
+                        return Err(invalid_value(
+                            key,
+                            value,
+                            "iso8601, unixepoch, unixepoch_ms or julianday",
+                        ))
+                    }
+                }
+            }
+            "nested" => {
+                self.nested = match value.to_ascii_lowercase().as_str() {
+                    "json" => NestedMode::Json,
+                    "flatten" => NestedMode::Flatten,
+                    _ => return Err(invalid_value(key, value, "json or flatten")),
+                }
+            }
+            "uint64_overflow" => self.uint64_overflow = Uint64Overflow::parse(key, value)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+pub fn invalid_value(key: &str, value: &str, expected: &str) -> Error {
+    Error::new_message(format!("invalid {} value '{}', expected {}", key, value, expected).as_str())
+}
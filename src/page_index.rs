@@ -0,0 +1,472 @@
+use parquet::{
+    column::page::{Page, PageMetadata, PageReader},
+    data_type::{ByteArray, FixedLenByteArray},
+    errors::{ParquetError, Result},
+    file::{
+        metadata::{ColumnChunkMetaData, RowGroupMetaData},
+        page_index::{
+            index::Index,
+            index_reader::{read_columns_indexes, read_pages_locations},
+        },
+        reader::{ChunkReader, RowGroupReader},
+        serialized_reader::SerializedPageReader,
+        statistics::Statistics,
+    },
+    format::{BoundaryOrder, PageLocation},
+    record::reader::RowIter,
+    schema::types::Type,
+};
+
+use std::{iter, ops::Range, slice, sync::Arc};
+
+use crate::errors::catch_panic;
+use crate::options::ReadOptions;
+use crate::pruning::{page_may_match, LeafConstraint};
+
+/// Rows of a row group, as sorted ranges of row numbers in it that don't
+/// overlap or touch.
+pub type RowRanges = Vec<Range<i64>>;
+
+/// A page's entry in a column index, with its min and max as statistics.
+pub struct IndexedPage {
+    pub statistics: Statistics,
+    /// Left out by some writers, unlike in statistics where it's 0 then
+    pub null_count: Option<i64>,
+}
+
+impl IndexedPage {
+    /// Whether the page has only NULLs, so has no min or max.
+    pub fn is_null_page(&self) -> bool {
+        !self.statistics.has_min_max_set()
+    }
+}
+
+/// A column chunk's column index: the stats of each of its pages, and
+/// how the pages are ordered by them.
+pub struct ColumnIndex {
+    pub pages: Vec<IndexedPage>,
+    pub boundary_order: BoundaryOrder,
+}
+
+impl ColumnIndex {
+    fn from_index(index: Index) -> Option<ColumnIndex> {
+        macro_rules! column_index {
+            ($index:expr, $convert:expr) => {
+                ColumnIndex {
+                    pages: $index
+                        .indexes
+                        .into_iter()
+                        .map(|page| IndexedPage {
+                            statistics: Statistics::new(
+                                page.min.map($convert),
+                                page.max.map($convert),
+                                None,
+                                page.null_count.unwrap_or(0).max(0) as u64,
+                                false,
+                            ),
+                            null_count: page.null_count,
+                        })
+                        .collect(),
+                    boundary_order: $index.boundary_order,
+                }
+            };
+        }
+        Some(match index {
+            Index::NONE => return None,
+            Index::BOOLEAN(index) => column_index!(index, |v| v),
+            Index::INT32(index) => column_index!(index, |v| v),
+            Index::INT64(index) => column_index!(index, |v| v),
+            Index::INT96(index) => column_index!(index, |v| v),
+            Index::FLOAT(index) => column_index!(index, |v| v),
+            Index::DOUBLE(index) => column_index!(index, |v| v),
+            Index::BYTE_ARRAY(index) => column_index!(index, ByteArray::from),
+            Index::FIXED_LEN_BYTE_ARRAY(index) => {
+                column_index!(index, |v: Vec<u8>| FixedLenByteArray::from(v))
+            }
+        })
+    }
+}
+
+/// The index readers panic on offsets and stats they can't use, like
+/// negative offsets or a min of the wrong size.
+fn read_index<T>(read: impl FnOnce() -> Result<T>) -> Result<T> {
+    catch_panic(read).unwrap_or_else(|_| {
+        Err(ParquetError::General(
+            "could not decode page index".to_owned(),
+        ))
+    })
+}
+
+/// Reads a column chunk's column index, when it has one.
+pub fn read_column_index<R: ChunkReader>(
+    chunks: &R,
+    chunk: &ColumnChunkMetaData,
+) -> Result<Option<ColumnIndex>> {
+    if chunk.column_index_offset().is_none() || chunk.column_index_length().is_none() {
+        return Ok(None);
+    }
+    let index = read_index(|| read_columns_indexes(chunks, slice::from_ref(chunk)))?;
+    Ok(index.into_iter().next().and_then(ColumnIndex::from_index))
+}
+
+/// Reads the locations of a column chunk's pages from its offset index,
+/// when it has one.
+pub fn read_offset_index<R: ChunkReader>(
+    chunks: &R,
+    chunk: &ColumnChunkMetaData,
+) -> Result<Option<Vec<PageLocation>>> {
+    if chunk.offset_index_offset().is_none() || chunk.offset_index_length().is_none() {
+        return Ok(None);
+    }
+    let locations = read_index(|| read_pages_locations(chunks, slice::from_ref(chunk)))?;
+    Ok(locations.into_iter().next())
+}
+
+/// The rows of each page, from where the page locations say they start.
+pub fn page_rows(
+    locations: &[PageLocation],
+    num_rows: i64,
+) -> impl Iterator<Item = Range<i64>> + '_ {
+    locations.iter().enumerate().map(move |(i, location)| {
+        let end = locations
+            .get(i + 1)
+            .map_or(num_rows, |next| next.first_row_index);
+        location.first_row_index..end
+    })
+}
+
+/// Every row of a row group with `num_rows` rows.
+pub fn all_rows(num_rows: i64) -> RowRanges {
+    iter::once(0..num_rows).collect()
+}
+
+/// Merges ranges that overlap or touch, keeping them sorted.
+fn merge(mut ranges: RowRanges) -> RowRanges {
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: RowRanges = vec![];
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// The rows in both `a` and `b`.
+pub fn intersect(a: &[Range<i64>], b: &[Range<i64>]) -> RowRanges {
+    let mut rows = vec![];
+    let (mut i, mut j) = (0, 0);
+    while let (Some(x), Some(y)) = (a.get(i), b.get(j)) {
+        let start = x.start.max(y.start);
+        let end = x.end.min(y.end);
+        if start < end {
+            rows.push(start..end);
+        }
+        if x.end < y.end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    rows
+}
+
+/// How many rows there are in the ranges.
+pub fn count_rows(rows: &[Range<i64>]) -> i64 {
+    rows.iter().map(|range| range.end - range.start).sum()
+}
+
+/// How many of the rows come before row `row`.
+pub fn count_rows_before(rows: &[Range<i64>], row: i64) -> i64 {
+    rows.iter()
+        .map(|range| (row.min(range.end) - range.start).max(0))
+        .sum()
+}
+
+/// The `n`th of the rows, from 0, and the index of the range it's in.
+pub fn nth_row(rows: &[Range<i64>], mut n: i64) -> Option<(usize, i64)> {
+    for (i, range) in rows.iter().enumerate() {
+        if n < range.end - range.start {
+            return Some((i, range.start + n));
+        }
+        n -= range.end - range.start;
+    }
+    None
+}
+
+/// Whether any of the rows are in `range`.
+fn overlaps(rows: &[Range<i64>], range: &Range<i64>) -> bool {
+    let i = rows.partition_point(|row| row.end <= range.start);
+    rows.get(i).is_some_and(|row| row.start < range.end)
+}
+
+/// The page index of the column chunks of a row group that a scan uses.
+pub struct RowGroupIndex {
+    column_indexes: Vec<Option<ColumnIndex>>,
+    offset_indexes: Vec<Option<Vec<PageLocation>>>,
+}
+
+impl RowGroupIndex {
+    /// Reads the column and offset indexes of the `leaves` column chunks.
+    pub fn read<R: ChunkReader>(
+        chunks: &R,
+        row_group: &RowGroupMetaData,
+        leaves: &[usize],
+    ) -> Result<RowGroupIndex> {
+        let mut index = RowGroupIndex {
+            column_indexes: (0..row_group.num_columns()).map(|_| None).collect(),
+            offset_indexes: vec![None; row_group.num_columns()],
+        };
+        for leaf in leaves {
+            let chunk = row_group.column(*leaf);
+            index.column_indexes[*leaf] = read_column_index(chunks, chunk)?;
+            index.offset_indexes[*leaf] = read_offset_index(chunks, chunk)?;
+        }
+        Ok(index)
+    }
+
+    /// The rows whose pages could match every constraint, judging from the
+    /// column index. Constraints on columns without one keep every row.
+    pub fn select_rows(
+        &self,
+        row_group: &RowGroupMetaData,
+        constraints: &[LeafConstraint],
+        options: &ReadOptions,
+    ) -> RowRanges {
+        let num_rows = row_group.num_rows();
+        let mut rows = all_rows(num_rows);
+        for constraint in constraints {
+            let (column_index, locations) = match (
+                &self.column_indexes[constraint.leaf],
+                &self.offset_indexes[constraint.leaf],
+            ) {
+                (Some(column_index), Some(locations))
+                    if column_index.pages.len() == locations.len() =>
+                {
+                    (column_index, locations)
+                }
+                _ => continue,
+            };
+            let descr = row_group.column(constraint.leaf).column_descr();
+            let matching = page_rows(locations, num_rows)
+                .zip(&column_index.pages)
+                .filter(|(page_rows, page)| {
+                    let page_num_rows = page_rows.end - page_rows.start;
+                    page_may_match(descr, page, page_num_rows, constraint, options)
+                })
+                .map(|(page_rows, _)| page_rows)
+                .collect();
+            rows = intersect(&rows, &merge(matching));
+        }
+        rows
+    }
+
+    /// Widens `rows` to whole pages of every one of the `leaves`, so each of
+    /// their pages is either read whole or skipped whole, and their values
+    /// line up into the same rows. `None` when a leaf has no offset index.
+    pub fn align_to_pages(
+        &self,
+        mut rows: RowRanges,
+        leaves: &[usize],
+        num_rows: i64,
+    ) -> Option<RowRanges> {
+        // widening to one leaf's pages can split another's, so this goes
+        // round until no leaf widens the rows any more
+        loop {
+            let before = rows.clone();
+            for leaf in leaves {
+                let pages: RowRanges =
+                    page_rows(self.offset_indexes[*leaf].as_ref()?, num_rows).collect();
+                rows = merge(
+                    rows.iter()
+                        .map(|range| {
+                            let first = pages.partition_point(|page| page.end <= range.start);
+                            let last = pages.partition_point(|page| page.start < range.end);
+                            let start = pages.get(first).map_or(range.start, |page| page.start);
+                            let end = match last {
+                                0 => range.end,
+                                last => pages[last - 1].end.max(range.end),
+                            };
+                            start.min(range.start)..end
+                        })
+                        .collect(),
+                );
+            }
+            if rows == before {
+                return Some(rows);
+            }
+        }
+    }
+
+    /// How many of the leaves' pages have any of the rows, and how many
+    /// pages they have in all.
+    pub fn count_pages(
+        &self,
+        rows: &[Range<i64>],
+        leaves: &[usize],
+        num_rows: i64,
+    ) -> (usize, usize) {
+        leaves
+            .iter()
+            .filter_map(|leaf| self.offset_indexes[*leaf].as_ref())
+            .fold((0, 0), |(read, total), locations| {
+                let read_pages = page_rows(locations, num_rows)
+                    .filter(|page| overlaps(rows, page))
+                    .count();
+                (read + read_pages, total + locations.len())
+            })
+    }
+
+    /// Compressed size of the leaves' pages that have any of the rows, and
+    /// of their dictionary pages.
+    pub fn read_bytes(
+        &self,
+        row_group: &RowGroupMetaData,
+        rows: &[Range<i64>],
+        leaves: &[usize],
+    ) -> i64 {
+        leaves
+            .iter()
+            .map(|leaf| {
+                let chunk = row_group.column(*leaf);
+                let locations = match &self.offset_indexes[*leaf] {
+                    Some(locations) => locations,
+                    None => return chunk.compressed_size(),
+                };
+                let (start, _) = chunk.byte_range();
+                let dictionary = locations
+                    .first()
+                    .map_or(0, |page| page.offset - start as i64);
+                dictionary
+                    + page_rows(locations, row_group.num_rows())
+                        .zip(locations)
+                        .filter(|(page, _)| overlaps(rows, page))
+                        .map(|(_, location)| location.compressed_page_size as i64)
+                        .sum::<i64>()
+            })
+            .sum()
+    }
+
+    /// Reads only the rows of a row group, which have to be whole pages of
+    /// every leaf that's read, like from [`RowGroupIndex::align_to_pages`].
+    pub fn select<R: ChunkReader + 'static>(
+        &self,
+        chunks: Arc<R>,
+        row_group: &RowGroupMetaData,
+        rows: RowRanges,
+    ) -> Result<SelectedRowGroup<R>> {
+        let metadata = RowGroupMetaData::builder(row_group.schema_descr_ptr())
+            .set_num_rows(count_rows(&rows))
+            .set_total_byte_size(row_group.total_byte_size())
+            .set_column_metadata(row_group.columns().to_vec())
+            .build()?;
+        Ok(SelectedRowGroup {
+            chunks,
+            metadata,
+            num_rows: row_group.num_rows() as usize,
+            offset_indexes: self.offset_indexes.clone(),
+            rows: Arc::new(rows),
+        })
+    }
+}
+
+/// A row group read through only some of its pages. It has only their
+/// rows, so row readers built on it never see the rest.
+pub struct SelectedRowGroup<R: ChunkReader> {
+    chunks: Arc<R>,
+    /// The row group's metadata, but with only the selected rows
+    metadata: RowGroupMetaData,
+    /// All of the row group's rows, which its last page ends at
+    num_rows: usize,
+    offset_indexes: Vec<Option<Vec<PageLocation>>>,
+    rows: Arc<RowRanges>,
+}
+
+impl<R: ChunkReader + 'static> RowGroupReader for SelectedRowGroup<R> {
+    fn metadata(&self) -> &RowGroupMetaData {
+        &self.metadata
+    }
+
+    fn num_columns(&self) -> usize {
+        self.metadata.num_columns()
+    }
+
+    fn get_column_page_reader(&self, i: usize) -> Result<Box<dyn PageReader>> {
+        let locations = self.offset_indexes[i]
+            .clone()
+            .ok_or_else(|| ParquetError::General(format!("column {} has no offset index", i)))?;
+        let pages = SerializedPageReader::new(
+            self.chunks.clone(),
+            self.metadata.column(i),
+            self.num_rows,
+            Some(locations),
+        )?;
+        Ok(Box::new(SelectedPages {
+            pages,
+            rows: self.rows.clone(),
+            next_row: 0,
+        }))
+    }
+
+    fn get_row_iter(&self, projection: Option<Type>) -> Result<RowIter<'_>> {
+        RowIter::from_row_group(projection, self)
+    }
+}
+
+/// Reads the pages of a column chunk that have any of the rows, and its
+/// dictionary page, skipping the rest without reading them.
+struct SelectedPages<R: ChunkReader> {
+    pages: SerializedPageReader<R>,
+    rows: Arc<RowRanges>,
+    /// The first row of the next data page
+    next_row: i64,
+}
+
+impl<R: ChunkReader> SelectedPages<R> {
+    /// Skips pages up to the next one that's read, returning its metadata.
+    fn skip_to_next(&mut self) -> Result<Option<PageMetadata>> {
+        while let Some(page) = self.pages.peek_next_page()? {
+            let rows = self.next_row..self.next_row + page.num_rows as i64;
+            if page.is_dict || overlaps(&self.rows, &rows) {
+                return Ok(Some(page));
+            }
+            self.pages.skip_next_page()?;
+            self.next_row = rows.end;
+        }
+        Ok(None)
+    }
+}
+
+impl<R: ChunkReader> Iterator for SelectedPages<R> {
+    type Item = Result<Page>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.get_next_page().transpose()
+    }
+}
+
+impl<R: ChunkReader> PageReader for SelectedPages<R> {
+    fn get_next_page(&mut self) -> Result<Option<Page>> {
+        match self.skip_to_next()? {
+            Some(page) => {
+                self.next_row += page.num_rows as i64;
+                self.pages.get_next_page()
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn peek_next_page(&mut self) -> Result<Option<PageMetadata>> {
+        self.skip_to_next()
+    }
+
+    fn skip_next_page(&mut self) -> Result<()> {
+        if let Some(page) = self.skip_to_next()? {
+            self.next_row += page.num_rows as i64;
+            self.pages.skip_next_page()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use parquet::{
+    basic::{Encoding, PageType},
+    errors::ParquetError,
+    file::{
+        metadata::ParquetMetaData,
+        reader::ChunkReader,
+        statistics::{from_thrift, Statistics},
+    },
+    format::{self, PageHeader},
+    schema::types::SchemaDescPtr,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
+};
+use thrift::protocol::TCompactInputProtocol;
+
+use std::{io::Read, mem, os::raw::c_int, rc::Rc};
+
+use crate::errors::{catch_panic, parquet_error};
+use crate::options::{ReadOptions, Uint64Overflow};
+use crate::source::{open_path, source_best_index, trace_aux, Source};
+use crate::stats::{result_stat, stats_bounds};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      source hidden,
+      row_group integer,
+      column text,
+      page integer,
+      page_type text,
+      offset integer,
+      num_values integer,
+      num_nulls integer,
+      num_rows integer,
+      encoding text,
+      compressed_size integer,
+      uncompressed_size integer,
+      stats_min,
+      stats_max,
+      stats_null_count integer,
+      stats_distinct integer,
+      uint64_overflow hidden
+    )";
+enum Columns {
+    Source,
+    RowGroup,
+    Column,
+    Page,
+    PageType,
+    Offset,
+    NumValues,
+    NumNulls,
+    NumRows,
+    Encoding,
+    CompressedSize,
+    UncompressedSize,
+    StatsMin,
+    StatsMax,
+    StatsNullCount,
+    StatsDistinct,
+    Uint64Overflow,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Source),
+        1 => Some(Columns::RowGroup),
+        2 => Some(Columns::Column),
+        3 => Some(Columns::Page),
+        4 => Some(Columns::PageType),
+        5 => Some(Columns::Offset),
+        6 => Some(Columns::NumValues),
+        7 => Some(Columns::NumNulls),
+        8 => Some(Columns::NumRows),
+        9 => Some(Columns::Encoding),
+        10 => Some(Columns::CompressedSize),
+        11 => Some(Columns::UncompressedSize),
+        12 => Some(Columns::StatsMin),
+        13 => Some(Columns::StatsMax),
+        14 => Some(Columns::StatsNullCount),
+        15 => Some(Columns::StatsDistinct),
+        16 => Some(Columns::Uint64Overflow),
+        _ => None,
+    }
+}
+
+/// A page of a column chunk, as its header describes it.
+struct PageRow {
+    row_group: usize,
+    leaf: usize,
+    /// Index of the page in its column chunk, from 0
+    page: usize,
+    /// Where the page's header starts in the file
+    offset: u64,
+    header: PageHeader,
+    statistics: Option<Statistics>,
+}
+
+impl PageRow {
+    /// The number of values and encoding from the header of the page's
+    /// type, which index pages don't have.
+    fn values(&self) -> Option<(i32, format::Encoding)> {
+        let header = &self.header;
+        if let Some(h) = &header.data_page_header {
+            Some((h.num_values, h.encoding))
+        } else if let Some(h) = &header.data_page_header_v2 {
+            Some((h.num_values, h.encoding))
+        } else {
+            let h = header.dictionary_page_header.as_ref()?;
+            Some((h.num_values, h.encoding))
+        }
+    }
+
+    /// Stats of data pages, as they're stored.
+    fn thrift_statistics(&self) -> Option<&format::Statistics> {
+        let header = &self.header;
+        match (&header.data_page_header, &header.data_page_header_v2) {
+            (Some(h), _) => h.statistics.as_ref(),
+            (None, Some(h)) => h.statistics.as_ref(),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Reads the header of every page of every column chunk. Only headers are
+/// read, pages aren't decompressed or decoded.
+fn read_page_headers<R: ChunkReader>(
+    chunks: &R,
+    metadata: &ParquetMetaData,
+    name: &str,
+) -> Result<Vec<PageRow>> {
+    let error = |message: String| parquet_error(name, ParquetError::General(message));
+    let mut pages = vec![];
+    for (row_group, row_group_metadata) in metadata.row_groups().iter().enumerate() {
+        for (leaf, chunk) in row_group_metadata.columns().iter().enumerate() {
+            let (start, length) = chunk.byte_range();
+            let end = start + length;
+            let mut offset = start;
+            let mut page = 0;
+            while offset < end {
+                let remaining = end - offset;
+                let mut read = chunks
+                    .get_read(offset, remaining as usize)
+                    .map_err(|err| parquet_error(name, err))?
+                    .take(remaining);
+                let header =
+                    PageHeader::read_from_in_protocol(&mut TCompactInputProtocol::new(&mut read))
+                        .map_err(|err| {
+                        error(format!("could not read page header at {}: {}", offset, err))
+                    })?;
+                let header_size = remaining - read.limit();
+                let page_size = u64::try_from(header.compressed_page_size)
+                    .map_err(|_| error(format!("page at {} has a negative size", offset)))?;
+                let mut row = PageRow {
+                    row_group,
+                    leaf,
+                    page,
+                    offset,
+                    header,
+                    statistics: None,
+                };
+                // the conversion panics on stats of the wrong size
+                let stats = row.thrift_statistics().cloned();
+                row.statistics = catch_panic(|| from_thrift(chunk.column_type(), stats))
+                    .ok()
+                    .flatten();
+                pages.push(row);
+                offset += header_size + page_size;
+                page += 1;
+            }
+        }
+    }
+    Ok(pages)
+}
+
+#[repr(C)]
+pub struct PagesTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for PagesTable {
+    type Aux = Rc<Trace>;
+    type Cursor = PagesCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, PagesTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), PagesTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(
+            info,
+            &[Columns::Source as c_int, Columns::Uint64Overflow as c_int],
+            1,
+        )
+    }
+
+    fn open(&mut self) -> Result<PagesCursor> {
+        Ok(PagesCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct PagesCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    schema_descr: Option<SchemaDescPtr>,
+    pages: Vec<PageRow>,
+    /// The file's path, for errors
+    path: String,
+    /// How stats are read, like the `parquet` table reads values
+    options: ReadOptions,
+    page_idx: usize,
+}
+
+impl PagesCursor {
+    fn new(trace: Rc<Trace>) -> PagesCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        PagesCursor {
+            base,
+            trace,
+            schema_descr: None,
+            pages: vec![],
+            path: String::new(),
+            options: ReadOptions::default(),
+            page_idx: 0,
+        }
+    }
+}
+
+impl VTabCursor for PagesCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        self.options.uint64_overflow = match values.get(1) {
+            Some(value) => Uint64Overflow::parse("uint64_overflow", api::value_text(value)?)?,
+            None => Uint64Overflow::default(),
+        };
+        self.path = source.name().to_owned();
+        let reader = source.open(&self.trace)?;
+        let metadata = reader.metadata();
+        self.pages = match &source {
+            Source::Path(path) => read_page_headers(&open_path(path)?, metadata, path)?,
+            Source::Blob(blob) => read_page_headers(blob, metadata, source.name())?,
+        };
+        self.schema_descr = Some(metadata.file_metadata().schema_descr_ptr());
+        self.page_idx = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.page_idx += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.page_idx >= self.pages.len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let (page, schema_descr) = match (self.pages.get(self.page_idx), &self.schema_descr) {
+            (Some(page), Some(schema_descr)) => (page, schema_descr),
+            _ => return Ok(()),
+        };
+        let descr = schema_descr.column(page.leaf);
+        let header = &page.header;
+        let bounds = page.statistics.as_ref().and_then(stats_bounds);
+        let stats = page.thrift_statistics();
+        let values = page.values();
+        let v2 = header.data_page_header_v2.as_ref();
+        match column(i) {
+            Some(Columns::Source) | Some(Columns::Uint64Overflow) => (),
+            Some(Columns::RowGroup) => api::result_int64(context, page.row_group as i64),
+            Some(Columns::Column) => api::result_text(context, descr.path().string())?,
+            Some(Columns::Page) => api::result_int64(context, page.page as i64),
+            Some(Columns::PageType) => match PageType::try_from(header.type_) {
+                Ok(page_type) => api::result_text(context, page_type.to_string())?,
+                Err(_) => api::result_int(context, header.type_.0),
+            },
+            Some(Columns::Offset) => api::result_int64(context, page.offset as i64),
+            Some(Columns::NumValues) => {
+                if let Some((num_values, _)) = values {
+                    api::result_int(context, num_values);
+                }
+            }
+            Some(Columns::NumNulls) => {
+                if let Some(v2) = v2 {
+                    api::result_int(context, v2.num_nulls);
+                }
+            }
+            Some(Columns::NumRows) => {
+                if let Some(v2) = v2 {
+                    api::result_int(context, v2.num_rows);
+                }
+            }
+            Some(Columns::Encoding) => {
+                if let Some((_, encoding)) = values {
+                    match Encoding::try_from(encoding) {
+                        Ok(encoding) => api::result_text(context, encoding.to_string())?,
+                        Err(_) => api::result_int(context, encoding.0),
+                    }
+                }
+            }
+            Some(Columns::CompressedSize) => api::result_int(context, header.compressed_page_size),
+            Some(Columns::UncompressedSize) => {
+                api::result_int(context, header.uncompressed_page_size)
+            }
+            Some(Columns::StatsMin) => {
+                if let Some((min, _)) = bounds {
+                    result_stat(context, &descr, min, &self.options, &self.path)?;
+                }
+            }
+            Some(Columns::StatsMax) => {
+                if let Some((_, max)) = bounds {
+                    result_stat(context, &descr, max, &self.options, &self.path)?;
+                }
+            }
+            Some(Columns::StatsNullCount) => {
+                if let Some(null_count) = stats.and_then(|stats| stats.null_count) {
+                    api::result_int64(context, null_count);
+                }
+            }
+            Some(Columns::StatsDistinct) => {
+                if let Some(distinct) = stats.and_then(|stats| stats.distinct_count) {
+                    api::result_int64(context, distinct);
+                }
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.page_idx as i64)
+    }
+}
@@ -1,52 +1,652 @@
 use parquet::{
-    file::{
-        reader::{FileReader, SerializedFileReader},
-        statistics::Statistics,
+    basic::{ConvertedType, LogicalType},
+    file::reader::{ChunkReader, FileReader, RowGroupReader, SerializedFileReader},
+    record::{
+        reader::{ReaderIter, TreeBuilder},
+        Field, Row,
     },
-    record::{reader::RowIter, Field, Row},
+    schema::types::{ColumnDescPtr, SchemaDescPtr, SchemaDescriptor, Type, TypePtr},
 };
 use sqlite_loadable::prelude::*;
 use sqlite_loadable::{
     api,
-    table::{ConstraintOperator, IndexInfo, VTab, VTabArguments, VTabCursor},
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
     BestIndexError, Error, Result,
 };
 
-use std::{fs::File, marker::PhantomData, mem, os::raw::c_int};
+use std::{
+    fs::File,
+    marker::PhantomData,
+    mem,
+    os::raw::c_int,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use chrono::{NaiveDate, NaiveDateTime};
+use bytes::Bytes;
+use chrono::NaiveDate;
+
+use crate::bloom::row_group_may_contain;
+use crate::errors::{catch_panic, column_error, decode_error, parquet_error};
+use crate::footer::PatchedChunks;
+use crate::hive::{discover_partitions, partition_root, PartitionColumn};
+use crate::logical::{bson_to_json, format_interval, format_uuid};
+use crate::options::{invalid_value, DecimalMode, ReadOptions};
+use crate::page_index::{
+    all_rows, count_rows, count_rows_before, intersect, nth_row, RowGroupIndex, RowRanges,
+};
+use crate::pruning::{
+    row_group_may_match, value_may_match, LeafConstraint, Operator, RowidRange, Scalar,
+};
+use crate::raw::{is_raw, read_raw, to_field, RawValue};
+use crate::schema::{declared_type, file_columns, find_field, union_columns, TableColumn};
+use crate::source::open_path;
+use crate::timestamps::{
+    format_time, int96_nanos, time_unit, timestamp_scalar, TimestampType, Unit, INT96_TIMESTAMP,
+};
+use crate::trace::{Level, Trace};
+
+/// Subtype SQLite's JSON functions give their results, and take as JSON.
+const JSON_SUBTYPE: u8 = b'J';
+
+/// A parquet file a table reads, the rowid of its first row, and its
+/// values for the table's partition columns.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    path: String,
+    first_rowid: i64,
+    num_rows: i64,
+    partitions: Vec<Option<Scalar>>,
+}
 
 #[repr(C)]
 pub struct ParquetCursor<'vtab> {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
-    //reader: Box<SerializedFileReader<File>>,
-    iter: Option<RowIter<'vtab>>,
+    files: Vec<SourceFile>,
+    /// Files left after pruning, and how many of them were started
+    scan_files: Vec<usize>,
+    file: usize,
+    /// The file being read, and its path
+    source: usize,
+    path: String,
+    reader: Option<SerializedFileReader<PatchedChunks<File>>>,
+    /// The same file, for reading its bloom filters, page indexes and the
+    /// pages they leave, when it has any
+    chunks: Option<Arc<File>>,
+    columns: Vec<TableColumn>,
+    options: ReadOptions,
+    /// Table columns the query references
+    used: Vec<usize>,
+    /// Pushed-down constraints on table columns, checked against each
+    /// file's statistics
+    constraints: Vec<(usize, Operator, Option<Scalar>)>,
+    /// The constraints on the current file's leaf columns
+    leaf_constraints: Vec<LeafConstraint>,
+    /// Schema of only the columns the query references, built per file
+    projection: Option<SchemaDescPtr>,
+    /// For each table column, its position inside the projected row, if
+    /// the current file has it
+    positions: Vec<Option<usize>>,
+    /// Leaf columns of the projection, the column chunks that get read
+    projected_leaves: Vec<usize>,
+    /// Table columns read with column readers instead of the row API
+    raw_columns: Vec<usize>,
+    /// The raw columns the query uses, with their leaf in the current file
+    raw_leaves: Vec<(usize, usize)>,
+    /// Values of `raw_leaves` in the current row group, one per row read
+    raw_values: Vec<(usize, Vec<Option<RawValue>>)>,
+    /// Row groups of the current file left after pruning, and how many of
+    /// them were started
+    row_groups: Vec<usize>,
+    row_group: usize,
+    /// Rowid of the first row in the current row group
+    row_group_first: i64,
+    /// Rowid of the first row in each row group of the current file
+    row_group_offsets: Vec<i64>,
+    rowids: RowidRange,
+    rows: Option<ReaderIter>,
+    /// The rows of the current row group that `rows` reads, the range the
+    /// current row is in, and how many rows were read before it
+    selection: RowRanges,
+    range: usize,
+    position: i64,
     current: Option<Row>,
-    eof: bool,
+    rowid: i64,
+    trace: Rc<Trace>,
+    /// Time spent decoding the current row group, when tracing it
+    decode_time: Option<Duration>,
     phantom: PhantomData<&'vtab ParquetTable>,
 }
-use std::time::Instant;
-
 impl ParquetCursor<'_> {
-    fn new<'vtab>(path: &str) -> ParquetCursor<'vtab> {
-        let file = File::open(&path).unwrap();
-        let reader = SerializedFileReader::new(file).unwrap();
-        let iter = RowIter::from_file_into(Box::new(reader));
-
+    fn new<'vtab>(
+        files: &[SourceFile],
+        columns: &[TableColumn],
+        raw_columns: &[usize],
+        options: ReadOptions,
+        trace: Rc<Trace>,
+    ) -> ParquetCursor<'vtab> {
         let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
-        let mut cursor = ParquetCursor {
+        ParquetCursor {
             base,
-            //reader: Box::new(reader),
-            iter: Some(iter),
+            files: files.to_vec(),
+            scan_files: vec![],
+            file: 0,
+            source: 0,
+            path: String::new(),
+            reader: None,
+            chunks: None,
+            columns: columns.to_vec(),
+            options,
+            used: vec![],
+            constraints: vec![],
+            leaf_constraints: vec![],
+            projection: None,
+            positions: vec![None; columns.len()],
+            projected_leaves: vec![],
+            raw_columns: raw_columns.to_vec(),
+            raw_leaves: vec![],
+            raw_values: vec![],
+            row_groups: vec![],
+            row_group: 0,
+            row_group_first: 0,
+            row_group_offsets: vec![],
+            rowids: RowidRange::default(),
+            rows: None,
+            selection: vec![],
+            range: 0,
+            position: -1,
             current: None,
-            eof: false,
+            rowid: -1,
+            trace,
+            decode_time: None,
             phantom: PhantomData,
+        }
+    }
+
+    /// Opens the next file left after pruning, and picks which of its row
+    /// groups to read.
+    fn next_file(&mut self) -> Result<()> {
+        self.source = self.scan_files[self.file];
+        self.file += 1;
+        let file = &self.files[self.source];
+        let first_rowid = file.first_rowid;
+        self.path = file.path.clone();
+        let reader = open_file(&self.path, &self.trace)?;
+
+        // columns are found by name, since files can differ with union_by_name
+        let schema = reader.metadata().file_metadata().schema();
+        let schema_descr = reader.metadata().file_metadata().schema_descr();
+        let found: Vec<bool> = self
+            .columns
+            .iter()
+            .map(|column| find_field(schema, &column.path).is_some())
+            .collect();
+        let leaf_of = |column: &TableColumn| {
+            (0..schema_descr.num_columns())
+                .find(|leaf| schema_descr.column(*leaf).path().parts() == column.path)
+        };
+        let row_columns: Vec<usize> = self
+            .used
+            .iter()
+            .copied()
+            .filter(|i| found[*i] && !self.raw_columns.contains(i))
+            .collect();
+        // struct leaves share their top-level field in the projected row
+        let mut projected: Vec<&str> = vec![];
+        self.positions.iter_mut().for_each(|p| *p = None);
+        for column in &row_columns {
+            let top = self.columns[*column].path[0].as_str();
+            let position = match projected.iter().position(|name| *name == top) {
+                Some(position) => position,
+                None => {
+                    projected.push(top);
+                    projected.len() - 1
+                }
+            };
+            self.positions[*column] = Some(position);
+        }
+        let paths: Vec<&[String]> = row_columns
+            .iter()
+            .map(|i| self.columns[*i].path.as_slice())
+            .collect();
+        let projection = projected_schema(&self.path, schema, &projected, &paths)?;
+        self.raw_leaves = self
+            .raw_columns
+            .iter()
+            .filter(|column| self.used.contains(column))
+            .filter_map(|column| Some((*column, leaf_of(&self.columns[*column])?)))
+            .collect();
+        self.projected_leaves = (0..schema_descr.num_columns())
+            .filter(|leaf| {
+                let path = schema_descr.column(*leaf).path().parts().to_vec();
+                projection
+                    .columns()
+                    .iter()
+                    .any(|column| column.path().parts() == path)
+                    || self.raw_leaves.iter().any(|(_, raw)| raw == leaf)
+            })
+            .collect();
+        self.projection = Some(projection);
+
+        // a column the file doesn't have is all NULLs
+        let mut missing_match = true;
+        let mut constraints = vec![];
+        for (column, operator, value) in &self.constraints {
+            if !found[*column] {
+                missing_match &= *operator == Operator::IsNull;
+            } else if let Some(leaf) = leaf_of(&self.columns[*column]) {
+                constraints.push(LeafConstraint {
+                    leaf,
+                    operator: *operator,
+                    value: value.clone(),
+                });
+            }
+        }
+
+        let row_groups = reader.metadata().row_groups();
+        self.row_group_offsets = row_groups
+            .iter()
+            .scan(first_rowid, |offset, row_group| {
+                let first = *offset;
+                *offset += row_group.num_rows();
+                Some(first)
+            })
+            .collect();
+        self.row_groups = row_groups
+            .iter()
+            .enumerate()
+            .filter(|(i, row_group)| {
+                missing_match
+                    && self
+                        .rowids
+                        .overlaps(self.row_group_offsets[*i], row_group.num_rows())
+                    && row_group_may_match(row_group, &constraints, &self.options)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.trace
+            .event(Level::Info, "prune row groups", &self.path)
+            .message(|| {
+                format!(
+                    "reading {} of {} row groups, {} of {} columns",
+                    self.row_groups.len(),
+                    row_groups.len(),
+                    row_columns.len() + self.raw_leaves.len(),
+                    self.columns.len(),
+                )
+            })
+            .finish();
+        let has_index = row_groups.iter().any(|row_group| {
+            row_group.columns().iter().any(|chunk| {
+                chunk.offset_index_offset().is_some() || chunk.bloom_filter_offset().is_some()
+            })
+        });
+        self.chunks = if has_index {
+            Some(Arc::new(open_path(&self.path)?))
+        } else {
+            None
         };
-        let start = Instant::now();
-        cursor.next().unwrap();
-        println!("x {:?}", start.elapsed());
-        cursor
+        self.leaf_constraints = constraints;
+        self.reader = Some(reader);
+        self.row_group = 0;
+        Ok(())
+    }
+
+    /// Starts decoding the next row group that has any rows, with only the
+    /// projected columns, moving on to the next file when needed. Leaves
+    /// `rows` empty once every file is read. Row groups bloom filters rule
+    /// out and pages the page index rules out aren't read, and rows before
+    /// the first rowid in range are skipped over.
+    fn next_row_group(&mut self) -> Result<()> {
+        self.finish_row_group();
+        loop {
+            if let (Some(reader), Some(projection)) =
+                (self.reader.as_ref(), self.projection.as_ref())
+            {
+                while let Some(i) = self.row_groups.get(self.row_group).copied() {
+                    let row_group = reader
+                        .get_row_group(i)
+                        .map_err(|err| parquet_error(&self.path, err))?;
+                    self.row_group += 1;
+                    let metadata = row_group.metadata();
+                    let num_rows = metadata.num_rows();
+                    if num_rows == 0 {
+                        continue;
+                    }
+                    let start = Instant::now();
+                    if let Some(chunks) = self.chunks.as_ref() {
+                        let (may_contain, filter_bytes) = row_group_may_contain(
+                            &**chunks,
+                            metadata,
+                            &self.leaf_constraints,
+                            &self.options,
+                        )
+                        .map_err(|err| parquet_error(&self.path, err))?;
+                        if filter_bytes > 0 {
+                            self.trace
+                                .event(Level::Debug, "check bloom filters", &self.path)
+                                .row_group(i)
+                                .bytes(filter_bytes as i64)
+                                .message(|| {
+                                    if may_contain {
+                                        "the row group may match".to_owned()
+                                    } else {
+                                        "skipped the row group".to_owned()
+                                    }
+                                })
+                                .finish();
+                        }
+                        if !may_contain {
+                            continue;
+                        }
+                    }
+                    let first = self.row_group_offsets[i];
+                    let mut selection = all_rows(num_rows);
+                    let mut bytes = self
+                        .projected_leaves
+                        .iter()
+                        .map(|leaf| metadata.column(*leaf).compressed_size())
+                        .sum();
+                    let mut selected = None;
+                    let rowid_rows = (self.rowids.first - first).max(0)
+                        ..(self.rowids.last - first).saturating_add(1).min(num_rows);
+                    let narrowed = !self.leaf_constraints.is_empty() || rowid_rows != (0..num_rows);
+                    if let Some(chunks) = self.chunks.as_ref().filter(|_| narrowed) {
+                        let leaves: Vec<usize> = self
+                            .leaf_constraints
+                            .iter()
+                            .map(|constraint| constraint.leaf)
+                            .chain(self.projected_leaves.iter().copied())
+                            .collect();
+                        let index = RowGroupIndex::read(&**chunks, metadata, &leaves)
+                            .map_err(|err| parquet_error(&self.path, err))?;
+                        let rows = intersect(
+                            &[rowid_rows],
+                            &index.select_rows(metadata, &self.leaf_constraints, &self.options),
+                        );
+                        let aligned = if rows.is_empty() {
+                            Some(rows)
+                        } else {
+                            index.align_to_pages(rows, &self.projected_leaves, num_rows)
+                        };
+                        if let Some(aligned) = aligned {
+                            let (read, total) =
+                                index.count_pages(&aligned, &self.projected_leaves, num_rows);
+                            self.trace
+                                .event(Level::Debug, "prune pages", &self.path)
+                                .row_group(i)
+                                .message(|| {
+                                    format!(
+                                        "reading {} of {} pages, {} of {} rows",
+                                        read,
+                                        total,
+                                        count_rows(&aligned),
+                                        num_rows
+                                    )
+                                })
+                                .finish();
+                            if aligned.is_empty() {
+                                continue;
+                            }
+                            if aligned != selection {
+                                bytes =
+                                    index.read_bytes(metadata, &aligned, &self.projected_leaves);
+                                selected = Some(
+                                    index
+                                        .select(chunks.clone(), metadata, aligned.clone())
+                                        .map_err(|err| parquet_error(&self.path, err))?,
+                                );
+                                selection = aligned;
+                            }
+                        }
+                    }
+                    let group: &dyn RowGroupReader = match &selected {
+                        Some(selected) => selected,
+                        None => &*row_group,
+                    };
+                    let skip = count_rows_before(&selection, self.rowids.first - first);
+                    let rows = catch_panic(|| {
+                        let mut rows = TreeBuilder::new().as_iter(projection.clone(), group);
+                        if skip > 0 {
+                            rows.nth(skip as usize - 1);
+                        }
+                        rows
+                    })
+                    .map_err(|payload| decode_error(&self.path, i, payload))?;
+                    for (column, leaf) in &self.raw_leaves {
+                        let values = catch_panic(|| read_raw(group, *leaf))
+                            .map_err(|payload| decode_error(&self.path, i, payload))?
+                            .map_err(|err| parquet_error(&self.path, err))?;
+                        self.raw_values.push((*column, values));
+                    }
+                    // the rowid before the first row left after skipping
+                    let (range, row) = nth_row(&selection, skip).unwrap_or((selection.len(), 0));
+                    self.row_group_first = first;
+                    self.range = range;
+                    self.position = skip - 1;
+                    self.rowid = first + row - 1;
+                    self.trace
+                        .event(Level::Debug, "read row group", &self.path)
+                        .row_group(i)
+                        .bytes(bytes)
+                        .message(|| {
+                            format!("skipped {} rows", num_rows - count_rows(&selection) + skip)
+                        })
+                        .finish();
+                    self.selection = selection;
+                    self.rows = Some(rows);
+                    if self.trace.enabled(Level::Debug) {
+                        self.decode_time = Some(start.elapsed());
+                    }
+                    return Ok(());
+                }
+            }
+            self.reader = None;
+            self.chunks = None;
+            if self.file >= self.scan_files.len() {
+                return Ok(());
+            }
+            self.next_file()?;
+        }
+    }
+
+    /// Index of the hidden `_filename` column, after the file's columns and
+    /// any partition columns.
+    fn filename_column(&self) -> usize {
+        self.columns.len() + self.files.first().map_or(0, |file| file.partitions.len())
+    }
+
+    /// Stops reading the current row group, tracing how long it took.
+    fn finish_row_group(&mut self) {
+        self.rows = None;
+        self.raw_values.clear();
+        if let Some(elapsed) = self.decode_time.take() {
+            self.trace
+                .event(Level::Debug, "decode row group", &self.path)
+                .row_group(self.row_groups[self.row_group - 1])
+                .elapsed(elapsed)
+                .finish();
+        }
+    }
+}
+
+impl Drop for ParquetCursor<'_> {
+    fn drop(&mut self) {
+        self.finish_row_group();
+    }
+}
+
+/// What best_index decided for a scan, passed along to xFilter in idxStr
+/// as `columns;constraints`, ex `0,3,5;2:eq,-1:gt`. The constraint values
+/// are given to xFilter in the same order.
+#[derive(Debug, Default, PartialEq)]
+struct ScanPlan {
+    /// Table columns the query references
+    columns: Vec<usize>,
+    /// Table columns with a pushed-down constraint, and its operator.
+    /// Like in sqlite3_index_info, -1 is the rowid.
+    constraints: Vec<(i32, Operator)>,
+}
+
+impl ScanPlan {
+    fn to_idx_str(&self) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|i| i.to_string()).collect();
+        let constraints: Vec<String> = self
+            .constraints
+            .iter()
+            .map(|(column, op)| format!("{}:{}", column, op.code()))
+            .collect();
+        format!("{};{}", columns.join(","), constraints.join(","))
+    }
+
+    fn from_idx_str(idx_str: Option<&str>) -> ScanPlan {
+        let (columns, constraints) = idx_str
+            .unwrap_or_default()
+            .split_once(';')
+            .unwrap_or_default();
+        ScanPlan {
+            columns: columns.split(',').filter_map(|i| i.parse().ok()).collect(),
+            constraints: constraints
+                .split(',')
+                .filter_map(|c| {
+                    let (column, op) = c.split_once(':')?;
+                    Some((column.parse().ok()?, Operator::from_code(op)?))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Builds a schema that contains only the given top-level fields of the
+/// file schema, in that order, so only their leaf columns get decoded.
+/// Structs keep only the fields on the given column paths.
+fn projected_schema(
+    path: &str,
+    schema: &Type,
+    columns: &[&str],
+    paths: &[&[String]],
+) -> Result<SchemaDescPtr> {
+    let mut fields = columns
+        .iter()
+        .map(|name| {
+            let field = schema
+                .get_fields()
+                .iter()
+                .find(|field| field.name() == *name)
+                .ok_or_else(|| {
+                    Error::new_message(
+                        format!("parquet file '{}' no longer has a column '{}'", path, name)
+                            .as_str(),
+                    )
+                })?;
+            let paths: Vec<&[String]> = paths
+                .iter()
+                .filter(|column| column[0] == *name)
+                .map(|column| &column[1..])
+                .collect();
+            project_field(field, &paths).map_err(|err| parquet_error(path, err))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let projection = Type::group_type_builder(schema.name())
+        .with_fields(&mut fields)
+        .build()
+        .map_err(|err| parquet_error(path, err))?;
+    Ok(Arc::new(SchemaDescriptor::new(Arc::new(projection))))
+}
+
+/// Keeps only the parts of a field on the given paths below it, all of it
+/// when one of them is empty.
+fn project_field(field: &TypePtr, paths: &[&[String]]) -> parquet::errors::Result<TypePtr> {
+    if paths.iter().any(|path| path.is_empty()) || !field.is_group() {
+        return Ok(field.clone());
+    }
+    let mut fields = field
+        .get_fields()
+        .iter()
+        .filter_map(|child| {
+            let paths: Vec<&[String]> = paths
+                .iter()
+                .filter(|path| path[0] == child.name())
+                .map(|path| &path[1..])
+                .collect();
+            (!paths.is_empty()).then(|| project_field(child, &paths))
+        })
+        .collect::<parquet::errors::Result<Vec<_>>>()?;
+    let info = field.get_basic_info();
+    let projected = Type::group_type_builder(field.name())
+        .with_repetition(info.repetition())
+        .with_converted_type(info.converted_type())
+        .with_logical_type(info.logical_type())
+        .with_fields(&mut fields)
+        .build()?;
+    Ok(Arc::new(projected))
+}
+
+/// Opens a parquet file and reads its footer.
+pub fn open_file(path: &str, trace: &Trace) -> Result<SerializedFileReader<PatchedChunks<File>>> {
+    let start = Instant::now();
+    let file = File::open(path).map_err(|err| {
+        Error::new_message(format!("could not open parquet file '{}': {}", path, err).as_str())
+    })?;
+    let size = file.metadata().map(|m| m.len() as i64).ok();
+    let reader = new_reader(file, path)?;
+    let mut event = trace
+        .event(Level::Info, "open file", path)
+        .elapsed(start.elapsed())
+        .message(|| {
+            format!(
+                "{} rows in {} row groups",
+                reader.metadata().file_metadata().num_rows(),
+                reader.metadata().num_row_groups()
+            )
+        });
+    if let Some(size) = size {
+        event = event.bytes(size);
+    }
+    event.finish();
+    Ok(reader)
+}
+
+/// Reads a parquet file held in memory, like a blob from `readfile()`.
+/// `name` stands in for its path in errors.
+pub fn open_blob(
+    blob: Bytes,
+    name: &str,
+    trace: &Trace,
+) -> Result<SerializedFileReader<PatchedChunks<Bytes>>> {
+    let start = Instant::now();
+    let size = blob.len() as i64;
+    let reader = new_reader(blob, name)?;
+    trace
+        .event(Level::Info, "open file", name)
+        .elapsed(start.elapsed())
+        .message(|| {
+            format!(
+                "{} rows in {} row groups",
+                reader.metadata().file_metadata().num_rows(),
+                reader.metadata().num_row_groups()
+            )
+        })
+        .bytes(size)
+        .finish();
+    Ok(reader)
+}
+
+fn new_reader<R: ChunkReader + 'static>(
+    chunks: R,
+    path: &str,
+) -> Result<SerializedFileReader<PatchedChunks<R>>> {
+    // the metadata decoder panics on logical types it doesn't know, like
+    // FLOAT16, so they're stripped first, but it could panic on others
+    let chunks = PatchedChunks::new(chunks);
+    match catch_panic(|| SerializedFileReader::new(chunks)) {
+        Ok(reader) => reader.map_err(|err| parquet_error(path, err)),
+        Err(_) => Err(Error::new_message(
+            format!("parquet file '{}' has an unsupported logical type", path).as_str(),
+        )),
     }
 }
 
@@ -54,20 +654,118 @@ impl VTabCursor for ParquetCursor<'_> {
     fn filter(
         &mut self,
         _idx_num: c_int,
-        _idx_str: Option<&str>,
-        _values: &[*mut sqlite3_value],
+        idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
     ) -> Result<()> {
+        self.finish_row_group();
+        let plan = ScanPlan::from_idx_str(idx_str);
+        self.used = plan.columns;
+
+        let partition_columns = self.columns.len()..self.filename_column();
+        let mut partitions = vec![];
+        let mut filenames = vec![];
+        self.constraints = vec![];
+        self.rowids = RowidRange::default();
+        for ((column, operator), value) in plan.constraints.iter().zip(values) {
+            let value = Scalar::from_value(value);
+            if *column < 0 {
+                self.rowids.restrict(*operator, value.as_ref());
+            } else if *column as usize == self.filename_column() {
+                filenames.push((*operator, value));
+            } else if partition_columns.contains(&(*column as usize)) {
+                partitions.push((*column as usize - self.columns.len(), *operator, value));
+            } else {
+                self.constraints.push((*column as usize, *operator, value));
+            }
+        }
+
+        self.scan_files = self
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| {
+                let path = Scalar::Text(file.path.clone());
+                self.rowids.overlaps(file.first_rowid, file.num_rows)
+                    && filenames
+                        .iter()
+                        .all(|(operator, value)| value_may_match(&path, *operator, value.as_ref()))
+                    && partitions.iter().all(|(partition, operator, value)| {
+                        match &file.partitions[*partition] {
+                            Some(partition) => {
+                                value_may_match(partition, *operator, value.as_ref())
+                            }
+                            None => *operator == Operator::IsNull,
+                        }
+                    })
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if self.files.len() > 1 {
+            self.trace
+                .event(Level::Info, "prune files", "")
+                .message(|| {
+                    format!(
+                        "reading {} of {} files",
+                        self.scan_files.len(),
+                        self.files.len()
+                    )
+                })
+                .finish();
+        }
+        self.file = 0;
+        self.reader = None;
+        self.next_row_group()?;
+
+        self.next()?;
         Ok(())
     }
 
     fn next(&mut self) -> Result<()> {
-        //let iter = self.iter;
-
-        //let has_more = self.reader.read_record(&mut self.record).unwrap();
-        //self.eof = !has_more;
-        //let start = Instant::now();
-        self.current = self.iter.as_mut().unwrap().next();
-        //println!("1 {:?}", start.elapsed());
+        loop {
+            let start = self.decode_time.map(|_| Instant::now());
+            self.current = match self.rows.as_mut() {
+                Some(rows) => match catch_panic(|| rows.next()) {
+                    Ok(row) => row,
+                    Err(payload) => {
+                        self.rows = None;
+                        let row_group = self.row_groups[self.row_group - 1];
+                        return Err(decode_error(&self.path, row_group, payload));
+                    }
+                },
+                None => None,
+            };
+            if let (Some(decode_time), Some(start)) = (self.decode_time.as_mut(), start) {
+                *decode_time += start.elapsed();
+            }
+            if self.current.is_some() {
+                self.position += 1;
+                self.rowid += 1;
+                let row = self.rowid - self.row_group_first;
+                if self
+                    .selection
+                    .get(self.range)
+                    .is_some_and(|range| row >= range.end)
+                {
+                    // the next range of rows read, after pages that weren't
+                    self.range += 1;
+                    if let Some(range) = self.selection.get(self.range) {
+                        self.rowid = self.row_group_first + range.start;
+                    }
+                }
+                if self.rowid > self.rowids.last {
+                    // rowids only go up, so nothing after this is in range
+                    self.current = None;
+                    self.finish_row_group();
+                    self.row_group = self.row_groups.len();
+                    self.file = self.scan_files.len();
+                }
+                break;
+            }
+            if self.rows.is_none() {
+                break;
+            }
+            self.next_row_group()?;
+        }
         Ok(())
     }
 
@@ -76,203 +774,528 @@ impl VTabCursor for ParquetCursor<'_> {
     }
 
     fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
-        let row = self.current.as_ref().unwrap();
-        let field = row.get_column_iter().nth(i.try_into().unwrap()).unwrap().1;
-        match field {
-            Field::Null => {
-                api::result_null(context);
+        let i = i as usize;
+        if i == self.filename_column() {
+            api::result_text(context, &self.path)?;
+            return Ok(());
+        }
+        if i >= self.columns.len() {
+            match self.files[self.source]
+                .partitions
+                .get(i - self.columns.len())
+            {
+                Some(Some(value)) => value.result(context)?,
+                Some(None) | None => api::result_null(context),
             }
-            Field::Bool(b) => {
-                api::result_bool(context, *b);
+            return Ok(());
+        }
+        if let Some((_, values)) = self.raw_values.iter().find(|(column, _)| *column == i) {
+            return match values.get(self.position as usize) {
+                Some(Some(value)) => {
+                    let column = &self.columns[i];
+                    result_raw(
+                        context,
+                        value,
+                        &column.field,
+                        &self.options,
+                        &self.path,
+                        &column.name,
+                    )
+                }
+                _ => {
+                    api::result_null(context);
+                    Ok(())
+                }
+            };
+        }
+        let position = self.positions.get(i).copied().flatten();
+        let mut field = match (self.current.as_ref(), position) {
+            (Some(row), Some(position)) => row.get_column_iter().nth(position).map(|(_, f)| f),
+            _ => None,
+        };
+        // struct leaves are found by name below their top-level field
+        for name in &self.columns[i].path[1..] {
+            field = match field {
+                Some(Field::Group(row)) => row
+                    .get_column_iter()
+                    .find(|(child, _)| *child == name)
+                    .map(|(_, f)| f),
+                _ => None,
+            };
+        }
+        let field = match field {
+            Some(field) => field,
+            None => {
+                api::result_null(context);
+                return Ok(());
             }
+        };
+        let column = &self.columns[i];
+        result_field(
+            context,
+            field,
+            &column.field,
+            &self.options,
+            &self.path,
+            &column.name,
+        )
+    }
 
-            Field::Byte(value) => {
-                api::result_int(context, (*value).into());
-            }
-            Field::UByte(value) => {
-                api::result_int(context, (*value).into());
-            }
-            Field::Short(value) => {
-                api::result_int(context, (*value).into());
-            }
-            Field::UShort(value) => api::result_int(context, (*value).into()),
-            Field::Int(i) => {
-                api::result_int(context, *i);
-            }
-            Field::UInt(value) => match i32::try_from(*value) {
-                Ok(value) => api::result_int(context, value),
-                Err(_) => api::result_int64(context, (*value).into()),
-            },
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.rowid)
+    }
+}
 
-            Field::Long(value) => {
-                api::result_int64(context, *value);
+/// Returns a value read with the row API, in the form the table's options
+/// pick. `column` is the column's type, `path` and `name` are for errors.
+pub fn result_field(
+    context: *mut sqlite3_context,
+    field: &Field,
+    column: &Type,
+    options: &ReadOptions,
+    path: &str,
+    name: &str,
+) -> Result<()> {
+    let timestamp = TimestampType::of(column);
+    let (converted, logical) = match column.is_primitive() {
+        true => (
+            column.get_basic_info().converted_type(),
+            column.get_basic_info().logical_type(),
+        ),
+        false => (ConvertedType::NONE, None),
+    };
+    match field {
+        Field::Null => {
+            api::result_null(context);
+        }
+        Field::Bool(b) => {
+            api::result_bool(context, *b);
+        }
+
+        Field::Byte(value) => {
+            api::result_int(context, (*value).into());
+        }
+        Field::UByte(value) => {
+            api::result_int(context, (*value).into());
+        }
+        Field::Short(value) => {
+            api::result_int(context, (*value).into());
+        }
+        Field::UShort(value) => api::result_int(context, (*value).into()),
+        Field::Int(i) => {
+            api::result_int(context, *i);
+        }
+        Field::UInt(value) => match i32::try_from(*value) {
+            Ok(value) => api::result_int(context, value),
+            Err(_) => api::result_int64(context, (*value).into()),
+        },
+
+        Field::Long(value) => match timestamp {
+            Some(timestamp) => result_timestamp(context, *value, timestamp, options, path, name)?,
+            None => api::result_int64(context, *value),
+        },
+        Field::ULong(value) => {
+            let scalar = options
+                .uint64_overflow
+                .scalar(*value)
+                .map_err(|message| column_error(path, name, &message))?;
+            match scalar {
+                Some(scalar) => scalar.result(context)?,
+                None => api::result_null(context),
             }
-            Field::ULong(value) => {
-                match i64::try_from(*value) {
-                    Ok(value) => api::result_int64(context, value),
-                    Err(err) => {
-                        return Err(Error::new_message(
-                            format!("Value too large: {}", err).as_str(),
+        }
+
+        Field::Double(value) => {
+            api::result_double(context, *value);
+        }
+        Field::Float(value) => {
+            api::result_double(context, (*value).into());
+        }
+        Field::Decimal(value) => {
+            let unscaled = decimal_unscaled(value.data())
+                .ok_or_else(|| column_error(path, name, "decimal wider than 128 bits"))?;
+            match options.decimal {
+                DecimalMode::Text => {
+                    api::result_text(context, format_decimal(unscaled, value.scale()))?;
+                }
+                DecimalMode::Real => {
+                    let text = format_decimal(unscaled, value.scale());
+                    api::result_double(context, text.parse().unwrap_or(f64::NAN));
+                }
+                DecimalMode::IntScaled => match i64::try_from(unscaled) {
+                    Ok(unscaled) => api::result_int64(context, unscaled),
+                    Err(_) => {
+                        return Err(column_error(
+                            path,
+                            name,
+                            format!("decimal too large for an integer: {}", unscaled).as_str(),
                         ))
                     }
-                };
+                },
             }
+        }
 
-            Field::Double(value) => {
-                api::result_double(context, *value);
-            }
-            Field::Float(value) => {
-                api::result_double(context, (*value).into());
+        Field::Str(s) => {
+            api::result_text(context, s)?;
+            if converted == ConvertedType::JSON {
+                api::result_subtype(context, JSON_SUBTYPE);
             }
-            Field::Decimal(value) => {
-                //println!("{} {}", value.precision(), value.scale());
-                // TODO match on value, get i32/i64/bytes, then do something??
-                api::result_blob(context, value.data());
+        }
+        Field::Bytes(b) => match (converted, logical) {
+            (ConvertedType::BSON, _) => {
+                let json =
+                    bson_to_json(b.data()).map_err(|message| column_error(path, name, &message))?;
+                api::result_json(context, json)?;
             }
+            (_, Some(LogicalType::Uuid)) => match format_uuid(b.data()) {
+                Some(uuid) => api::result_text(context, uuid)?,
+                None => api::result_blob(context, b.data()),
+            },
+            _ => api::result_blob(context, b.data()),
+        },
+        Field::ListInternal(_) | Field::Group(_) | Field::MapInternal(_) => {
+            api::result_json(context, field.to_json_value())?;
+        }
+        Field::Date(value) => {
+            let f = format_date(*value as i32).ok_or_else(|| {
+                column_error(
+                    path,
+                    name,
+                    format!("date out of range: {} days", value).as_str(),
+                )
+            })?;
+            api::result_text(context, f)?;
+        }
+        Field::TimestampMillis(t) => {
+            let timestamp = timestamp.unwrap_or(TimestampType {
+                unit: Unit::Millis,
+                utc: true,
+            });
+            result_timestamp(context, *t as i64, timestamp, options, path, name)?;
+        }
+        Field::TimestampMicros(t) => {
+            let timestamp = timestamp.unwrap_or(TimestampType {
+                unit: Unit::Micros,
+                utc: true,
+            });
+            result_timestamp(context, *t as i64, timestamp, options, path, name)?;
+        }
+    }
+    Ok(())
+}
 
-            Field::Str(s) => {
-                api::result_text(context, s)?;
-            }
-            Field::Bytes(b) => {
-                api::result_blob(context, b.data());
-            }
-            Field::ListInternal(_) | Field::Group(_) | Field::MapInternal(_) => {
-                api::result_json(context, field.to_json_value())?;
-            }
-            Field::Date(value) => {
-                let ts = NaiveDate::from_num_days_from_ce(719163 + i32::try_from(*value).unwrap());
-                let f = ts.format("%Y-%m-%d");
-                api::result_text(context, &f.to_string())?;
-            }
-            Field::TimestampMillis(t) => {
-                api::result_int64(context, (*t).try_into().unwrap());
-            }
-            Field::TimestampMicros(t) => {
-                //api::result_int64((*t).try_into().unwrap());
-                let ts = NaiveDateTime::from_timestamp(
-                    (*t / 1000000).try_into().unwrap(),
-                    u32::try_from(*t % 1000000).unwrap() * 1000,
-                );
-                let f = ts.format("%Y-%m-%d %H:%M:%S.%3f");
-                api::result_text(context, &f.to_string())?;
-            }
+/// Returns a value read with a column reader, like [`result_field`] does.
+pub fn result_raw(
+    context: *mut sqlite3_context,
+    value: &RawValue,
+    column: &Type,
+    options: &ReadOptions,
+    path: &str,
+    name: &str,
+) -> Result<()> {
+    let out_of_range = || column_error(path, name, "value out of range");
+    if let Some(unit) = time_unit(column) {
+        let time = match value {
+            RawValue::Int32(value) => format_time((*value).into(), unit),
+            RawValue::Int64(value) => format_time(*value, unit),
+            _ => None,
+        };
+        api::result_text(context, time.ok_or_else(out_of_range)?)?;
+        return Ok(());
+    }
+    match value {
+        RawValue::Int96(value) => {
+            let nanos = int96_nanos(value).ok_or_else(out_of_range)?;
+            result_timestamp(context, nanos, INT96_TIMESTAMP, options, path, name)?;
         }
-        Ok(())
+        RawValue::Bytes(value) => {
+            let interval = format_interval(value.data()).ok_or_else(out_of_range)?;
+            api::result_text(context, interval)?;
+        }
+        RawValue::Bool(value) => api::result_bool(context, *value),
+        RawValue::Int32(value) => api::result_int(context, *value),
+        RawValue::Int64(value) => api::result_int64(context, *value),
+        RawValue::Float(value) => api::result_double(context, (*value).into()),
+        RawValue::Double(value) => api::result_double(context, *value),
     }
+    Ok(())
+}
 
-    fn rowid(&self) -> Result<i64> {
-        Ok(1)
+/// Returns a value of a leaf column read with a column reader, converted
+/// like the row API would for the types it can decode.
+pub fn result_leaf(
+    context: *mut sqlite3_context,
+    descr: &ColumnDescPtr,
+    value: RawValue,
+    options: &ReadOptions,
+    path: &str,
+    name: &str,
+) -> Result<()> {
+    let column = descr.self_type();
+    if is_raw(column) {
+        return result_raw(context, &value, column, options, path, name);
+    }
+    // the conversions panic on invalid UTF-8
+    let field = catch_panic(|| to_field(descr, value))
+        .map_err(|_| column_error(path, name, "value can't be decoded"))?;
+    result_field(context, &field, column, options, path, name)
+}
+
+/// Returns a timestamp in the form `timestamps=` picks.
+fn result_timestamp(
+    context: *mut sqlite3_context,
+    value: i64,
+    timestamp: TimestampType,
+    options: &ReadOptions,
+    path: &str,
+    name: &str,
+) -> Result<()> {
+    timestamp_scalar(value, timestamp, options.timestamps)
+        .ok_or_else(|| {
+            column_error(
+                path,
+                name,
+                format!("timestamp out of range: {}", value).as_str(),
+            )
+        })?
+        .result(context)
+}
+
+/// Reads the unscaled value of a DECIMAL from its big-endian two's
+/// complement bytes, or `None` when it doesn't fit in 128 bits.
+pub fn decimal_unscaled(bytes: &[u8]) -> Option<i128> {
+    let negative = matches!(bytes.first(), Some(byte) if byte & 0x80 != 0);
+    let fill = if negative { 0xff } else { 0x00 };
+    // sign extension bytes past 128 bits don't change the value
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != fill)
+        .unwrap_or(bytes.len())
+        .min(bytes.len().saturating_sub(16));
+    let bytes = &bytes[start..];
+    if bytes.len() > 16 {
+        return None;
+    }
+    let mut buf = [fill; 16];
+    buf[16 - bytes.len()..].copy_from_slice(bytes);
+    Some(i128::from_be_bytes(buf))
+}
+
+/// Formats an unscaled DECIMAL with `scale` digits after the point, ex
+/// `-5` with a scale of 2 as `-0.05`.
+pub fn format_decimal(unscaled: i128, scale: i32) -> String {
+    let digits = unscaled.unsigned_abs().to_string();
+    let sign = if unscaled < 0 { "-" } else { "" };
+    let scale = match usize::try_from(scale) {
+        Ok(scale) if scale > 0 => scale,
+        _ => return format!("{}{}", sign, digits),
+    };
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+/// Formats a DATE, in days since the unix epoch, as `YYYY-MM-DD`.
+pub fn format_date(days: i32) -> Option<String> {
+    let date = NaiveDate::from_num_days_from_ce_opt(719163 + days)?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+/// Strips the quotes around an argument value, if it has any.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['\'', '"'] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
     }
+    value
+}
+
+/// Expands a `filename=` argument into the files it names. Patterns with
+/// glob characters match files in sorted order, directories stand for every
+/// `.parquet` file under them, and anything else is a path.
+fn expand_filename(pattern: &str) -> Result<Vec<String>> {
+    if Path::new(pattern).is_dir() {
+        let pattern = format!("{}/**/*.parquet", pattern.trim_end_matches('/'));
+        return expand_filename(&pattern);
+    }
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![pattern.to_owned()]);
+    }
+    let paths = glob::glob(pattern).map_err(|err| {
+        Error::new_message(format!("invalid filename pattern '{}': {}", pattern, err).as_str())
+    })?;
+    let mut files = vec![];
+    for path in paths {
+        let path = path.map_err(|err| {
+            Error::new_message(
+                format!("could not read files matching '{}': {}", pattern, err).as_str(),
+            )
+        })?;
+        files.push(path.to_string_lossy().into_owned());
+    }
+    if files.is_empty() {
+        return Err(Error::new_message(
+            format!("no parquet files match '{}'", pattern).as_str(),
+        ));
+    }
+    Ok(files)
 }
 
 #[repr(C)]
 pub struct ParquetTable {
     /// must be first
     base: sqlite3_vtab,
-    path: String,
+    files: Vec<SourceFile>,
+    columns: Vec<TableColumn>,
+    /// Columns read with column readers, since the row API can't decode them
+    raw_columns: Vec<usize>,
+    /// Columns from `key=value` directories, after the file's columns
+    partitions: Vec<PartitionColumn>,
+    options: ReadOptions,
+    trace: Rc<Trace>,
 }
 
 impl<'vtab> VTab<'vtab> for ParquetTable {
-    type Aux = ();
+    type Aux = Rc<Trace>;
     type Cursor = ParquetCursor<'vtab>;
 
     fn connect(
         _db: *mut sqlite3,
-        _aux: Option<&()>,
+        aux: Option<&Rc<Trace>>,
         args: VTabArguments,
     ) -> Result<(String, ParquetTable)> {
-        let mut path = None;
+        let trace = aux
+            .cloned()
+            .ok_or_else(|| Error::new_message("parquet is missing its trace"))?;
+        let mut paths: Vec<String> = vec![];
+        // the directory each file was found under, for its partitions
+        let mut roots: Vec<PathBuf> = vec![];
+        let mut union_by_name = false;
+        let mut options = ReadOptions::default();
         for arg in args.arguments {
-            let mut split = arg.trim().split('=');
-            let _key = split.next().unwrap();
-            let value = split.next().unwrap();
-            let mut chars = value.chars();
-            chars.next();
-            chars.next_back();
-            let value = chars.as_str();
-            path = Some(value.to_owned());
-        }
-        let path = path.unwrap();
-        let file = File::open(&path).unwrap();
-        let reader = SerializedFileReader::new(file).unwrap();
-        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+            let (key, value) = match arg.trim().split_once('=') {
+                Some((key, value)) => (key.trim(), unquote(value)),
+                None => ("", ""),
+            };
+            match key {
+                "filename" => {
+                    for path in expand_filename(value)? {
+                        if !paths.contains(&path) {
+                            paths.push(path);
+                            roots.push(partition_root(value));
+                        }
+                    }
+                }
+                "union_by_name" => {
+                    union_by_name = match value.to_ascii_lowercase().as_str() {
+                        "1" | "true" => true,
+                        "0" | "false" => false,
+                        _ => return Err(invalid_value(key, value, "0 or 1")),
+                    }
+                }
+                _ if options.set(key, value)? => (),
+                _ => {
+                    return Err(Error::new_message(
+                        format!(
+                            "invalid parquet argument '{}', expected filename='...'",
+                            arg
+                        )
+                        .as_str(),
+                    ))
+                }
+            };
+        }
+        if paths.is_empty() {
+            return Err(Error::new_message(
+                "parquet tables need a filename='...' argument",
+            ));
+        }
 
-        let vtab = ParquetTable { base, path };
+        let mut files: Vec<SourceFile> = vec![];
+        let mut schemas: Vec<TypePtr> = vec![];
+        for path in &paths {
+            let reader = open_file(path, &trace)?;
+            let file_metadata = reader.metadata().file_metadata();
+            // without union_by_name, every file needs the first file's columns
+            if !union_by_name
+                && !schemas.is_empty()
+                && file_metadata.schema().get_fields() != schemas[0].get_fields()
+            {
+                return Err(Error::new_message(
+                    format!(
+                        "parquet file '{}' has a different schema than '{}', \
+                        use union_by_name=1 to combine columns by name",
+                        path, paths[0]
+                    )
+                    .as_str(),
+                ));
+            }
+            let first_rowid = files.last().map_or(0, |f| f.first_rowid + f.num_rows);
+            files.push(SourceFile {
+                path: path.clone(),
+                first_rowid,
+                num_rows: file_metadata.num_rows(),
+                partitions: vec![],
+            });
+            schemas.push(file_metadata.schema_descr().root_schema_ptr());
+        }
+        let columns = if union_by_name {
+            let fields: Vec<(&str, &[TypePtr])> = paths
+                .iter()
+                .zip(&schemas)
+                .map(|(path, schema)| (path.as_str(), schema.get_fields()))
+                .collect();
+            union_columns(&fields, options.nested)?
+        } else {
+            file_columns(schemas[0].get_fields(), options.nested)
+        };
 
-        let mut sql = String::from("create table x(");
-        let metadata = reader.metadata();
-        let schema = metadata.file_metadata().schema();
-        let mut it = schema.get_fields().iter().peekable();
+        let raw_columns: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| is_raw(&column.field))
+            .map(|(i, _)| i)
+            .collect();
 
-        while let Some(field) = it.next() {
-            sql.push('"');
-            sql.push_str(field.name());
+        let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+        let (partitions, partition_values) = discover_partitions(&paths, &roots, &names);
+        for (file, values) in files.iter_mut().zip(partition_values) {
+            file.partitions = values;
+        }
+
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let mut sql = String::from("create table x(");
+        for column in &columns {
             sql.push('"');
-            if it.peek().is_some() {
-                sql.push(',');
+            sql.push_str(&column.name.replace('"', "\"\""));
+            sql.push_str("\" ");
+            sql.push_str(&declared_type(&column.field, &options));
+            if !column.nullable {
+                sql.push_str(" not null");
             }
+            sql.push(',');
         }
-        sql.push(')');
-
-        let fm = metadata.file_metadata();
-        //fm.
-        let rg = metadata.row_group(0);
-        println!(
-            "rgo: bytesize={}, comp={}",
-            rg.total_byte_size(),
-            rg.compressed_size(),
-        );
-        for column_chunk in rg.columns() {
-            let stats = column_chunk.statistics().unwrap();
-            print!(
-                "{} {} ",
-                column_chunk.column_path(),
-                stats.has_min_max_set()
-            );
-            match stats {
-                Statistics::Int32(ref typed) => {
-                    print!(
-                        "i32: [{}, {}] {:?} {}",
-                        typed.min(),
-                        typed.max(),
-                        stats.distinct_count(),
-                        stats.has_nulls()
-                    )
-                }
-                Statistics::Int64(ref typed) => {
-                    print!("i64: [{}, {}]", typed.min(), typed.max())
-                }
-                Statistics::Double(ref typed) => {
-                    print!("double: [{}, {}]", typed.min(), typed.max())
-                }
-                _ => (),
-            };
-            println!();
-        }
-
-        for col in fm.schema_descr().columns() {
-            println!(
-                "{} {}: {} {:?} {}",
-                col.name(),
-                col.path(),
-                col.physical_type(),
-                col.logical_type(),
-                col.converted_type(),
-            );
-        }
-        if let Some(metadata) = fm.key_value_metadata() {
-            for x in metadata {
-                println!("\t{} {:?}", x.key, x.value);
-            }
+        for partition in &partitions {
+            sql.push('"');
+            sql.push_str(&partition.name.replace('"', "\"\""));
+            sql.push_str("\" ");
+            sql.push_str(partition.sql_type);
+            sql.push(',');
         }
+        let vtab = ParquetTable {
+            base,
+            files,
+            columns,
+            raw_columns,
+            partitions,
+            options,
+            trace,
+        };
+        // the file each row comes from, last so it doesn't shift column indices
+        sql.push_str("\"_filename\" hidden)");
 
-        println!(
-            "version: {}, rows: {}, rowgroups:{}, created_by:{:?}",
-            fm.version(),
-            fm.num_rows(),
-            metadata.num_row_groups(),
-            fm.created_by(),
-        );
         Ok((sql, vtab))
     }
     fn destroy(&self) -> Result<()> {
@@ -280,14 +1303,69 @@ impl<'vtab> VTab<'vtab> for ParquetTable {
     }
 
     fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        // colUsed has one bit per column, and the last bit stands in for every
+        // column past the 63rd.
+        let used = info.columns_used();
+        let mut plan = ScanPlan {
+            columns: (0..self.columns.len())
+                .filter(|i| used & (1 << (*i).min(63)) != 0)
+                .collect(),
+            constraints: vec![],
+        };
+        // Constraints on primitive columns are checked against row group
+        // statistics in xFilter, and rowid, partition and _filename
+        // constraints pick which rows and files to read. SQLite still checks
+        // every row, since statistics only rule out whole row groups.
+        // Those all compare values byte by byte, so constraints under other
+        // collations, like `collate nocase`, are left to SQLite.
+        let mut rowid_eq = false;
+        for (i, mut constraint) in info.constraints().into_iter().enumerate() {
+            let column = constraint.column_idx();
+            let filename_column = self.columns.len() + self.partitions.len();
+            let pushable = match usize::try_from(column) {
+                Ok(column) if column >= self.columns.len() => column <= filename_column,
+                Ok(column) => self.columns[column].field.is_primitive(),
+                Err(_) => column == -1,
+            };
+            let binary = info
+                .collation(i)
+                .is_some_and(|collation| collation.eq_ignore_ascii_case("BINARY"));
+            if !pushable || !constraint.usable() || !binary {
+                continue;
+            }
+            // rowid and _filename are never NULL
+            let nullable = column >= 0 && column as usize != filename_column;
+            match Operator::from_constraint(constraint.op()) {
+                Some(Operator::IsNull) if !nullable => (),
+                Some(op) => {
+                    rowid_eq |= column == -1 && op == Operator::Eq;
+                    plan.constraints.push((column, op));
+                    constraint.set_argv_index(plan.constraints.len().try_into().unwrap());
+                }
+                None => (),
+            }
+        }
+        info.set_idxstr(&plan.to_idx_str())
+            .map_err(|_| BestIndexError::Error)?;
         info.set_idxnum(1);
-        info.set_estimated_rows(100000);
-        info.set_estimated_cost(100000.0);
+        if rowid_eq {
+            info.set_estimated_rows(1);
+            info.set_estimated_cost(1.0);
+        } else {
+            info.set_estimated_rows(100000);
+            info.set_estimated_cost(100000.0 / (1 + plan.constraints.len()) as f64);
+        }
 
         Ok(())
     }
 
     fn open(&mut self) -> Result<ParquetCursor<'_>> {
-        Ok(ParquetCursor::new(&self.path))
+        Ok(ParquetCursor::new(
+            &self.files,
+            &self.columns,
+            &self.raw_columns,
+            self.options,
+            self.trace.clone(),
+        ))
     }
 }
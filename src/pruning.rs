@@ -0,0 +1,492 @@
+use parquet::{
+    basic::{ConvertedType, Type as PhysicalType},
+    file::{metadata::RowGroupMetaData, statistics::Statistics},
+    schema::types::ColumnDescriptor,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, api::ValueType, table::ConstraintOperator};
+
+use std::cmp::Ordering;
+
+use crate::bloom::BloomFilter;
+use crate::options::{ReadOptions, Uint64Overflow};
+use crate::page_index::IndexedPage;
+use crate::parquet::format_date;
+use crate::timestamps::{format_time, time_unit, timestamp_scalar, TimestampType};
+
+/// The constraint operators that can be checked against row group statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    IsNull,
+}
+
+impl Operator {
+    pub fn from_constraint(op: Option<ConstraintOperator>) -> Option<Operator> {
+        match op? {
+            ConstraintOperator::EQ => Some(Operator::Eq),
+            ConstraintOperator::LT => Some(Operator::Lt),
+            ConstraintOperator::LE => Some(Operator::Le),
+            ConstraintOperator::GT => Some(Operator::Gt),
+            ConstraintOperator::GE => Some(Operator::Ge),
+            ConstraintOperator::ISNULL => Some(Operator::IsNull),
+            _ => None,
+        }
+    }
+
+    /// Short name used when passing constraints through idxStr.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Operator::Eq => "eq",
+            Operator::Lt => "lt",
+            Operator::Le => "le",
+            Operator::Gt => "gt",
+            Operator::Ge => "ge",
+            Operator::IsNull => "isnull",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Operator> {
+        match code {
+            "eq" => Some(Operator::Eq),
+            "lt" => Some(Operator::Lt),
+            "le" => Some(Operator::Le),
+            "gt" => Some(Operator::Gt),
+            "ge" => Some(Operator::Ge),
+            "isnull" => Some(Operator::IsNull),
+            _ => None,
+        }
+    }
+}
+
+/// A value as SQLite would see it, for comparing constraint values
+/// against statistics with SQLite's own ordering rules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    Integer(i128),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// Integers past this can't round-trip through an f64, so comparing
+/// them against a REAL isn't exact.
+const MAX_EXACT_FLOAT_INTEGER: i128 = 1 << 53;
+
+impl Scalar {
+    /// Reads a constraint value given to xFilter. NULL values have no
+    /// Scalar, since no comparison against NULL is ever true.
+    pub fn from_value(value: &*mut sqlite3_value) -> Option<Scalar> {
+        match api::value_type(value) {
+            ValueType::Integer => Some(Scalar::Integer(api::value_int64(value).into())),
+            ValueType::Float => Some(Scalar::Real(api::value_double(value))),
+            ValueType::Text => api::value_text(value)
+                .ok()
+                .map(|text| Scalar::Text(text.to_owned())),
+            ValueType::Blob => Some(Scalar::Blob(api::value_blob(value).to_vec())),
+            ValueType::Null => None,
+        }
+    }
+
+    /// Returns the value as the result of an SQL function or column.
+    pub fn result(&self, context: *mut sqlite3_context) -> sqlite_loadable::Result<()> {
+        match self {
+            Scalar::Integer(value) => api::result_int64(context, *value as i64),
+            Scalar::Real(value) => api::result_double(context, *value),
+            Scalar::Text(value) => api::result_text(context, value)?,
+            Scalar::Blob(value) => api::result_blob(context, value),
+        }
+        Ok(())
+    }
+
+    /// Orders two values like SQLite does, or `None` when they can't be
+    /// safely compared, like TEXT against INTEGER or inexact floats.
+    fn compare(&self, other: &Scalar) -> Option<Ordering> {
+        match (self, other) {
+            (Scalar::Integer(a), Scalar::Integer(b)) => Some(a.cmp(b)),
+            (Scalar::Real(a), Scalar::Real(b)) => a.partial_cmp(b),
+            (Scalar::Integer(a), Scalar::Real(b)) => {
+                if a.abs() > MAX_EXACT_FLOAT_INTEGER {
+                    return None;
+                }
+                (*a as f64).partial_cmp(b)
+            }
+            (Scalar::Real(_), Scalar::Integer(_)) => other.compare(self).map(Ordering::reverse),
+            (Scalar::Text(a), Scalar::Text(b)) => Some(a.as_bytes().cmp(b.as_bytes())),
+            (Scalar::Blob(a), Scalar::Blob(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+/// A pushed-down constraint on a leaf column, with the value it's compared against.
+pub struct LeafConstraint {
+    pub leaf: usize,
+    pub operator: Operator,
+    pub value: Option<Scalar>,
+}
+
+/// Text timestamps and dates only sort like their values inside 0000-9999.
+fn sortable_datetime(text: String) -> Option<Scalar> {
+    let bytes = text.as_bytes();
+    if bytes.len() >= 10 && bytes[0].is_ascii_digit() && bytes[4] == b'-' {
+        Some(Scalar::Text(text))
+    } else {
+        None
+    }
+}
+
+/// Converts a statistics min or max into the value the `parquet` table
+/// would return for it. Only types whose returned values sort the same
+/// way as their parquet values are supported.
+fn stat_scalar(
+    descr: &ColumnDescriptor,
+    stats: &Statistics,
+    max: bool,
+    options: &ReadOptions,
+) -> Option<Scalar> {
+    if !stats.has_min_max_set() {
+        return None;
+    }
+    macro_rules! bound {
+        ($typed:expr) => {
+            if max {
+                $typed.max()
+            } else {
+                $typed.min()
+            }
+        };
+    }
+    // times are formatted at a fixed width, so they sort like the integers
+    if let Some(unit) = time_unit(descr.self_type()) {
+        return match stats {
+            Statistics::Int32(typed) => format_time((*bound!(typed)).into(), unit),
+            Statistics::Int64(typed) => format_time(*bound!(typed), unit),
+            _ => None,
+        }
+        .map(Scalar::Text);
+    }
+    match stats {
+        Statistics::Boolean(typed) => Some(Scalar::Integer((*bound!(typed)).into())),
+        Statistics::Int32(typed) => {
+            let value = *bound!(typed);
+            match descr.converted_type() {
+                ConvertedType::NONE
+                | ConvertedType::INT_8
+                | ConvertedType::INT_16
+                | ConvertedType::INT_32 => Some(Scalar::Integer(value.into())),
+                ConvertedType::UINT_8 | ConvertedType::UINT_16 | ConvertedType::UINT_32 => {
+                    Some(Scalar::Integer((value as u32).into()))
+                }
+                ConvertedType::DATE => format_date(value).and_then(sortable_datetime),
+                _ => None,
+            }
+        }
+        Statistics::Int64(typed) => {
+            let value = *bound!(typed);
+            if let Some(timestamp) = TimestampType::of(descr.self_type()) {
+                return match timestamp_scalar(value, timestamp, options.timestamps)? {
+                    Scalar::Text(text) => sortable_datetime(text),
+                    scalar => Some(scalar),
+                };
+            }
+            match descr.converted_type() {
+                ConvertedType::NONE | ConvertedType::INT_64 => Some(Scalar::Integer(value.into())),
+                ConvertedType::UINT_64 => Some(Scalar::Integer((value as u64).into())),
+                _ => None,
+            }
+        }
+        Statistics::Float(typed) => {
+            let value = *bound!(typed);
+            (!value.is_nan()).then_some(Scalar::Real(value.into()))
+        }
+        Statistics::Double(typed) => {
+            let value = *bound!(typed);
+            (!value.is_nan()).then_some(Scalar::Real(value))
+        }
+        // legacy min/max were sorted as signed bytes, so they can't be trusted here
+        Statistics::ByteArray(_) if stats.is_min_max_deprecated() => None,
+        Statistics::ByteArray(typed) => {
+            let value = bound!(typed).data();
+            match descr.converted_type() {
+                ConvertedType::UTF8 | ConvertedType::ENUM | ConvertedType::JSON => {
+                    std::str::from_utf8(value)
+                        .ok()
+                        .map(|text| Scalar::Text(text.to_owned()))
+                }
+                ConvertedType::NONE => Some(Scalar::Blob(value.to_vec())),
+                _ => None,
+            }
+        }
+        Statistics::Int96(_) | Statistics::FixedLenByteArray(_) => None,
+    }
+}
+
+/// Whether the statistics of a column chunk leave any chance of a row
+/// matching the constraint. When unsure, this says yes.
+fn chunk_may_match(
+    descr: &ColumnDescriptor,
+    stats: &Statistics,
+    num_rows: i64,
+    constraint: &LeafConstraint,
+    options: &ReadOptions,
+) -> bool {
+    if constraint.operator == Operator::IsNull {
+        // too large UINT64s can read as NULL
+        if options.uint64_overflow == Uint64Overflow::Null
+            && descr.converted_type() == ConvertedType::UINT_64
+        {
+            return true;
+        }
+        if descr.max_def_level() == 0 {
+            return false;
+        }
+        // null_count defaults to 0 when a writer leaves it out, so a zero is
+        // only trusted next to a min/max.
+        return stats.has_nulls() || !stats.has_min_max_set();
+    }
+    let value = match &constraint.value {
+        Some(value) => value,
+        // comparing against NULL never matches
+        None => return false,
+    };
+    if stats.has_nulls() && stats.null_count() as i64 == num_rows {
+        return false;
+    }
+    let min = stat_scalar(descr, stats, false, options);
+    let max = stat_scalar(descr, stats, true, options);
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return true,
+    };
+    let (vs_min, vs_max) = match (value.compare(&min), value.compare(&max)) {
+        (Some(vs_min), Some(vs_max)) => (vs_min, vs_max),
+        _ => return true,
+    };
+    match constraint.operator {
+        Operator::Eq => vs_min != Ordering::Less && vs_max != Ordering::Greater,
+        Operator::Lt => vs_min == Ordering::Greater,
+        Operator::Le => vs_min != Ordering::Less,
+        Operator::Gt => vs_max == Ordering::Less,
+        Operator::Ge => vs_max != Ordering::Greater,
+        Operator::IsNull => unreachable!(),
+    }
+}
+
+/// Whether a page could have rows that match the constraint, judging from
+/// its entry in the column index. When unsure, this says yes.
+pub fn page_may_match(
+    descr: &ColumnDescriptor,
+    page: &IndexedPage,
+    num_rows: i64,
+    constraint: &LeafConstraint,
+    options: &ReadOptions,
+) -> bool {
+    if constraint.operator == Operator::IsNull {
+        // unlike in statistics, a missing null count isn't written as 0
+        if page.null_count.is_none() {
+            return true;
+        }
+    } else if page.is_null_page() {
+        return false;
+    }
+    chunk_may_match(descr, &page.statistics, num_rows, constraint, options)
+}
+
+/// The plain encoding of the parquet value that reads as `value`, which is
+/// what bloom filters hash, or `None` when there isn't exactly one, like
+/// for 0.0 and -0.0, or it isn't known. Byte arrays are their bytes
+/// without a length.
+pub fn plain_bytes(
+    descr: &ColumnDescriptor,
+    value: &Scalar,
+    options: &ReadOptions,
+) -> Option<Vec<u8>> {
+    if time_unit(descr.self_type()).is_some() || TimestampType::of(descr.self_type()).is_some() {
+        return None;
+    }
+    // REALs equal to an integer compare equal to it
+    let integer = match value {
+        Scalar::Integer(value) => Some(*value),
+        Scalar::Real(value) if value.fract() == 0.0 && value.abs() < 2f64.powi(63) => {
+            Some(*value as i128)
+        }
+        _ => None,
+    };
+    match (descr.physical_type(), descr.converted_type()) {
+        (
+            PhysicalType::INT32,
+            ConvertedType::NONE
+            | ConvertedType::INT_8
+            | ConvertedType::INT_16
+            | ConvertedType::INT_32,
+        ) => Some(i32::try_from(integer?).ok()?.to_le_bytes().to_vec()),
+        (
+            PhysicalType::INT32,
+            ConvertedType::UINT_8 | ConvertedType::UINT_16 | ConvertedType::UINT_32,
+        ) => Some(u32::try_from(integer?).ok()?.to_le_bytes().to_vec()),
+        (PhysicalType::INT64, ConvertedType::NONE | ConvertedType::INT_64) => {
+            Some(i64::try_from(integer?).ok()?.to_le_bytes().to_vec())
+        }
+        // past the largest INTEGER, values read as uint64_overflow says
+        (PhysicalType::INT64, ConvertedType::UINT_64) => {
+            let value = match value {
+                Scalar::Text(text) if options.uint64_overflow == Uint64Overflow::Text => text
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|value| *value > i64::MAX as u64)?,
+                _ => u64::try_from(integer?)
+                    .ok()
+                    .filter(|value| *value <= i64::MAX as u64)?,
+            };
+            Some(value.to_le_bytes().to_vec())
+        }
+        (PhysicalType::FLOAT, ConvertedType::NONE) => {
+            let value = match value {
+                Scalar::Real(value) => *value,
+                _ => integer.filter(|value| value.abs() <= MAX_EXACT_FLOAT_INTEGER)? as f64,
+            };
+            let float = value as f32;
+            (value != 0.0 && f64::from(float) == value).then(|| float.to_le_bytes().to_vec())
+        }
+        (PhysicalType::DOUBLE, ConvertedType::NONE) => {
+            let value = match value {
+                Scalar::Real(value) => *value,
+                _ => integer.filter(|value| value.abs() <= MAX_EXACT_FLOAT_INTEGER)? as f64,
+            };
+            (value != 0.0).then(|| value.to_le_bytes().to_vec())
+        }
+        // text that isn't valid UTF-8 reads as a BLOB
+        (
+            PhysicalType::BYTE_ARRAY,
+            ConvertedType::UTF8 | ConvertedType::ENUM | ConvertedType::JSON,
+        ) => match value {
+            Scalar::Text(text) => Some(text.as_bytes().to_vec()),
+            Scalar::Blob(blob) => Some(blob.clone()),
+            _ => None,
+        },
+        (PhysicalType::BYTE_ARRAY, ConvertedType::NONE) => match value {
+            Scalar::Blob(blob) => Some(blob.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether a column chunk could have rows that match the constraint,
+/// judging from its bloom filter, which only rules out equality. When
+/// unsure, this says yes.
+pub fn bloom_may_match(
+    descr: &ColumnDescriptor,
+    filter: &BloomFilter,
+    constraint: &LeafConstraint,
+    options: &ReadOptions,
+) -> bool {
+    if constraint.operator != Operator::Eq {
+        return true;
+    }
+    let value = match &constraint.value {
+        Some(value) => value,
+        // comparing against NULL never matches
+        None => return false,
+    };
+    match plain_bytes(descr, value, options) {
+        Some(bytes) => filter.check(&bytes),
+        None => true,
+    }
+}
+
+/// Whether a row group could contain rows that match every constraint,
+/// judging from its column chunk statistics.
+pub fn row_group_may_match(
+    row_group: &RowGroupMetaData,
+    constraints: &[LeafConstraint],
+    options: &ReadOptions,
+) -> bool {
+    constraints.iter().all(|constraint| {
+        let chunk = row_group.column(constraint.leaf);
+        match chunk.statistics() {
+            Some(stats) => chunk_may_match(
+                chunk.column_descr(),
+                stats,
+                row_group.num_rows(),
+                constraint,
+                options,
+            ),
+            None => true,
+        }
+    })
+}
+
+/// The inclusive range of rowids a scan can return, narrowed down by
+/// constraints on `rowid`. Rowids are row ordinals in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowidRange {
+    pub first: i64,
+    pub last: i64,
+}
+
+impl Default for RowidRange {
+    fn default() -> Self {
+        RowidRange {
+            first: 0,
+            last: i64::MAX,
+        }
+    }
+}
+
+impl RowidRange {
+    /// Narrows the range with a `rowid <op> value` constraint. Values that
+    /// aren't numbers are left for SQLite to check.
+    pub fn restrict(&mut self, operator: Operator, value: Option<&Scalar>) {
+        // the smallest rowid that's >= value, and the largest that's <= value
+        let (ceil, floor) = match value {
+            Some(Scalar::Integer(i)) => (*i, *i),
+            Some(Scalar::Real(f)) if !f.is_nan() => (f.ceil() as i128, f.floor() as i128),
+            Some(_) => return,
+            None => {
+                self.last = -1;
+                return;
+            }
+        };
+        let clamp = |i: i128| i.clamp(-1, i64::MAX.into()) as i64;
+        match operator {
+            Operator::Eq => {
+                self.first = self.first.max(clamp(ceil));
+                self.last = self.last.min(clamp(floor));
+            }
+            Operator::Gt => self.first = self.first.max(clamp(floor + 1)),
+            Operator::Ge => self.first = self.first.max(clamp(ceil)),
+            Operator::Lt => self.last = self.last.min(clamp(ceil - 1)),
+            Operator::Le => self.last = self.last.min(clamp(floor)),
+            Operator::IsNull => self.last = -1,
+        }
+    }
+
+    /// Whether any of the rowids `first..first + count` are in the range.
+    pub fn overlaps(&self, first: i64, count: i64) -> bool {
+        count > 0 && first <= self.last && first + count > self.first
+    }
+}
+
+/// Whether `value <op> target` could be true, for values known before
+/// reading anything, like a file name. When unsure, this says yes.
+pub fn value_may_match(value: &Scalar, operator: Operator, target: Option<&Scalar>) -> bool {
+    let target = match target {
+        Some(target) => target,
+        None => return false,
+    };
+    match (operator, value.compare(target)) {
+        (Operator::IsNull, _) => false,
+        (_, None) => true,
+        (Operator::Eq, Some(ordering)) => ordering == Ordering::Equal,
+        (Operator::Lt, Some(ordering)) => ordering == Ordering::Less,
+        (Operator::Le, Some(ordering)) => ordering != Ordering::Greater,
+        (Operator::Gt, Some(ordering)) => ordering == Ordering::Greater,
+        (Operator::Ge, Some(ordering)) => ordering != Ordering::Less,
+    }
+}
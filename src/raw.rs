@@ -0,0 +1,242 @@
+use parquet::{
+    basic::{ConvertedType, LogicalType, Type as PhysicalType},
+    column::reader::{ColumnReader, ColumnReaderImpl},
+    data_type::{ByteArray, DataType, FixedLenByteArray, Int96},
+    errors::{ParquetError, Result},
+    file::reader::{FileReader, RowGroupReader},
+    record::Field,
+    schema::types::{ColumnDescPtr, Type},
+};
+
+use crate::errors::{catch_panic, decode_error, parquet_error};
+
+/// A value of a column that's read with a column reader rather than the row
+/// API, which can't decode TIME or INTERVAL and cuts INT96 to milliseconds.
+#[derive(Debug, Clone)]
+pub enum RawValue {
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    Int96(Int96),
+    Float(f32),
+    Double(f64),
+    Bytes(ByteArray),
+}
+
+/// Whether a table column is read with a column reader. Only top-level
+/// primitive columns can be, nested ones are left to the row API.
+pub fn is_raw(field: &Type) -> bool {
+    if !field.is_primitive() {
+        return false;
+    }
+    let info = field.get_basic_info();
+    field.get_physical_type() == PhysicalType::INT96
+        || matches!(
+            info.converted_type(),
+            ConvertedType::TIME_MILLIS | ConvertedType::TIME_MICROS | ConvertedType::INTERVAL
+        )
+        || matches!(info.logical_type(), Some(LogicalType::Time { .. }))
+}
+
+/// The row API's value for a column it can decode, one `is_raw` is false
+/// for. Panics on invalid UTF-8, like the row API does.
+pub fn to_field(descr: &ColumnDescPtr, value: RawValue) -> Field {
+    match value {
+        RawValue::Bool(value) => Field::convert_bool(descr, value),
+        RawValue::Int32(value) => Field::convert_int32(descr, value),
+        RawValue::Int64(value) => Field::convert_int64(descr, value),
+        RawValue::Int96(value) => Field::convert_int96(descr, value),
+        RawValue::Float(value) => Field::convert_float(descr, value),
+        RawValue::Double(value) => Field::convert_double(descr, value),
+        RawValue::Bytes(value) => Field::convert_byte_array(descr, value),
+    }
+}
+
+/// Reads `num_levels` levels of a column chunk into `def_levels` and, when
+/// given, `rep_levels`, returning the values that aren't NULL.
+fn read_batches<T: DataType>(
+    reader: &mut ColumnReaderImpl<T>,
+    num_levels: usize,
+    def_levels: &mut [i16],
+    mut rep_levels: Option<&mut [i16]>,
+) -> Result<Vec<T::T>> {
+    let mut values = vec![T::T::default(); num_levels];
+    let (mut values_read, mut levels_read) = (0, 0);
+    while levels_read < num_levels {
+        let (values, levels) = reader.read_batch(
+            num_levels - levels_read,
+            Some(&mut def_levels[levels_read..]),
+            rep_levels.as_mut().map(|rep| &mut rep[levels_read..]),
+            &mut values[values_read..],
+        )?;
+        if levels == 0 {
+            return Err(ParquetError::EOF(format!(
+                "column chunk has {} of {} values",
+                levels_read, num_levels
+            )));
+        }
+        values_read += values;
+        levels_read += levels;
+    }
+    values.truncate(values_read);
+    Ok(values)
+}
+
+/// Reads every value of a column chunk, with `None` for NULLs.
+fn read_values<T: DataType>(
+    reader: &mut ColumnReaderImpl<T>,
+    num_rows: usize,
+    max_def_level: i16,
+) -> Result<Vec<Option<T::T>>> {
+    let mut def_levels = vec![max_def_level; num_rows];
+    // values are packed, without NULLs
+    let mut values = read_batches(reader, num_rows, &mut def_levels, None)?.into_iter();
+    Ok(def_levels
+        .iter()
+        .map(|level| {
+            if *level == max_def_level {
+                values.next()
+            } else {
+                None
+            }
+        })
+        .collect())
+}
+
+/// Values of a leaf column with their levels, for columns inside lists and
+/// maps, which have more than one value per row.
+#[derive(Debug, Default)]
+pub struct LeveledValues {
+    /// The values that aren't NULL, in order
+    pub values: Vec<RawValue>,
+    pub def_levels: Vec<i16>,
+    pub rep_levels: Vec<i16>,
+}
+
+macro_rules! with_reader {
+    ($reader:expr, |$typed:ident, $variant:ident| $body:expr) => {
+        match $reader {
+            ColumnReader::BoolColumnReader($typed) => {
+                let $variant = RawValue::Bool;
+                $body
+            }
+            ColumnReader::Int32ColumnReader($typed) => {
+                let $variant = RawValue::Int32;
+                $body
+            }
+            ColumnReader::Int64ColumnReader($typed) => {
+                let $variant = RawValue::Int64;
+                $body
+            }
+            ColumnReader::Int96ColumnReader($typed) => {
+                let $variant = RawValue::Int96;
+                $body
+            }
+            ColumnReader::FloatColumnReader($typed) => {
+                let $variant = RawValue::Float;
+                $body
+            }
+            ColumnReader::DoubleColumnReader($typed) => {
+                let $variant = RawValue::Double;
+                $body
+            }
+            ColumnReader::ByteArrayColumnReader($typed) => {
+                let $variant = RawValue::Bytes;
+                $body
+            }
+            ColumnReader::FixedLenByteArrayColumnReader($typed) => {
+                let $variant = |value: FixedLenByteArray| RawValue::Bytes(value.into());
+                $body
+            }
+        }
+    };
+}
+
+/// Reads every value of a leaf column in a row group, one per row.
+pub fn read_raw(row_group: &dyn RowGroupReader, leaf: usize) -> Result<Vec<Option<RawValue>>> {
+    let num_rows = row_group.metadata().num_rows() as usize;
+    let max_def_level = row_group
+        .metadata()
+        .column(leaf)
+        .column_descr()
+        .max_def_level();
+    let mut reader = row_group.get_column_reader(leaf)?;
+    Ok(with_reader!(&mut reader, |reader, variant| {
+        read_values(reader, num_rows, max_def_level)?
+            .into_iter()
+            .map(|value| value.map(variant))
+            .collect()
+    }))
+}
+
+/// Levels read at a time by a [`LeafScanner`].
+const BATCH_SIZE: usize = 1024;
+
+/// Reads one leaf column of a file a batch of levels at a time, one row
+/// group after another, so a column chunk is never decoded all at once.
+pub struct LeafScanner {
+    leaf: usize,
+    max_def_level: i16,
+    /// The next row group to start
+    row_group: usize,
+    reader: Option<ColumnReader>,
+    /// Levels of the current column chunk not read yet
+    levels_left: usize,
+}
+
+impl LeafScanner {
+    pub fn new(leaf: usize, max_def_level: i16) -> LeafScanner {
+        LeafScanner {
+            leaf,
+            max_def_level,
+            row_group: 0,
+            reader: None,
+            levels_left: 0,
+        }
+    }
+
+    /// Reads the next batch, with the index of its row group, or `None`
+    /// after the last row group. `path` is the file's, for errors.
+    pub fn next_batch(
+        &mut self,
+        file: &dyn FileReader,
+        path: &str,
+    ) -> sqlite_loadable::Result<Option<(usize, LeveledValues)>> {
+        catch_panic(|| self.read_batch(file))
+            .map_err(|payload| decode_error(path, self.row_group.saturating_sub(1), payload))?
+            .map_err(|err| parquet_error(path, err))
+    }
+
+    fn read_batch(&mut self, file: &dyn FileReader) -> Result<Option<(usize, LeveledValues)>> {
+        while self.levels_left == 0 {
+            if self.row_group >= file.num_row_groups() {
+                self.reader = None;
+                return Ok(None);
+            }
+            let row_group = file.get_row_group(self.row_group)?;
+            self.row_group += 1;
+            self.levels_left = row_group.metadata().column(self.leaf).num_values() as usize;
+            self.reader = Some(row_group.get_column_reader(self.leaf)?);
+        }
+        let reader = match self.reader.as_mut() {
+            Some(reader) => reader,
+            None => return Ok(None),
+        };
+        let num_levels = self.levels_left.min(BATCH_SIZE);
+        let mut def_levels = vec![self.max_def_level; num_levels];
+        let mut rep_levels = vec![0; num_levels];
+        let values = with_reader!(reader, |reader, variant| {
+            read_batches(reader, num_levels, &mut def_levels, Some(&mut rep_levels))?
+                .into_iter()
+                .map(variant)
+                .collect()
+        });
+        self.levels_left -= num_levels;
+        let values = LeveledValues {
+            values,
+            def_levels,
+            rep_levels,
+        };
+        Ok(Some((self.row_group - 1, values)))
+    }
+}
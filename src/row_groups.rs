@@ -0,0 +1,208 @@
+use parquet::format::RowGroup;
+use serde_json::json;
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Result,
+};
+
+use std::{mem, os::raw::c_int, rc::Rc};
+
+use crate::source::{source_best_index, trace_aux, Source};
+use crate::trace::Trace;
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+      source hidden,
+      row_group integer,
+      num_rows integer,
+      total_byte_size integer,
+      compressed_size integer,
+      file_offset integer,
+      ordinal integer,
+      sorting_columns
+    )";
+#[allow(clippy::enum_variant_names)]
+enum Columns {
+    Source,
+    RowGroup,
+    NumRows,
+    TotalByteSize,
+    CompressedSize,
+    FileOffset,
+    Ordinal,
+    SortingColumns,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Source),
+        1 => Some(Columns::RowGroup),
+        2 => Some(Columns::NumRows),
+        3 => Some(Columns::TotalByteSize),
+        4 => Some(Columns::CompressedSize),
+        5 => Some(Columns::FileOffset),
+        6 => Some(Columns::Ordinal),
+        7 => Some(Columns::SortingColumns),
+        _ => None,
+    }
+}
+
+#[repr(C)]
+pub struct RowGroupsTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for RowGroupsTable {
+    type Aux = Rc<Trace>;
+    type Cursor = RowGroupsCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, RowGroupsTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = trace_aux(aux)?;
+        Ok((CREATE_SQL.to_owned(), RowGroupsTable { base, trace }))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        source_best_index(info, &[Columns::Source as c_int], 1)
+    }
+
+    fn open(&mut self) -> Result<RowGroupsCursor> {
+        Ok(RowGroupsCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct RowGroupsCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    /// Row groups as they're stored, which keep the fields the parquet
+    /// crate drops, like `ordinal`
+    row_groups: Vec<RowGroup>,
+    /// Dotted paths of the leaf columns, which sorting columns refer to
+    leaf_paths: Vec<String>,
+    row_group_idx: usize,
+}
+
+impl RowGroupsCursor {
+    fn new(trace: Rc<Trace>) -> RowGroupsCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        RowGroupsCursor {
+            base,
+            trace,
+            row_groups: vec![],
+            leaf_paths: vec![],
+            row_group_idx: 0,
+        }
+    }
+}
+
+/// Bytes of compressed data in a row group. Older writers leave out the
+/// total, so it's summed from the column chunks.
+fn compressed_size(row_group: &RowGroup) -> i64 {
+    row_group.total_compressed_size.unwrap_or_else(|| {
+        row_group
+            .columns
+            .iter()
+            .filter_map(|chunk| chunk.meta_data.as_ref())
+            .map(|meta| meta.total_compressed_size)
+            .sum()
+    })
+}
+
+/// Where a row group's first page starts, which is its first column
+/// chunk's dictionary page, or first data page when it has none.
+fn file_offset(row_group: &RowGroup) -> Option<i64> {
+    row_group.file_offset.or_else(|| {
+        row_group
+            .columns
+            .iter()
+            .filter_map(|chunk| chunk.meta_data.as_ref())
+            .map(|meta| meta.dictionary_page_offset.unwrap_or(meta.data_page_offset))
+            .min()
+    })
+}
+
+impl VTabCursor for RowGroupsCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let source = Source::from_value(values.first())?;
+        let reader = source.open(&self.trace)?;
+        let schema_descr = reader.metadata().file_metadata().schema_descr();
+        self.leaf_paths = (0..schema_descr.num_columns())
+            .map(|leaf| schema_descr.column(leaf).path().string())
+            .collect();
+        self.row_groups = source.file_metadata()?.row_groups;
+        self.row_group_idx = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.row_group_idx += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row_group_idx >= self.row_groups.len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let row_group = match self.row_groups.get(self.row_group_idx) {
+            Some(row_group) => row_group,
+            None => return Ok(()),
+        };
+        match column(i) {
+            Some(Columns::Source) => (),
+            Some(Columns::RowGroup) => api::result_int64(context, self.row_group_idx as i64),
+            Some(Columns::NumRows) => api::result_int64(context, row_group.num_rows),
+            Some(Columns::TotalByteSize) => api::result_int64(context, row_group.total_byte_size),
+            Some(Columns::CompressedSize) => api::result_int64(context, compressed_size(row_group)),
+            Some(Columns::FileOffset) => {
+                if let Some(offset) = file_offset(row_group) {
+                    api::result_int64(context, offset);
+                }
+            }
+            Some(Columns::Ordinal) => {
+                if let Some(ordinal) = row_group.ordinal {
+                    api::result_int(context, ordinal.into());
+                }
+            }
+            Some(Columns::SortingColumns) => {
+                if let Some(sorting_columns) = &row_group.sorting_columns {
+                    let sorting_columns: Vec<_> = sorting_columns
+                        .iter()
+                        .map(|sorting| {
+                            json!({
+                                "column": usize::try_from(sorting.column_idx)
+                                    .ok()
+                                    .and_then(|leaf| self.leaf_paths.get(leaf)),
+                                "descending": sorting.descending,
+                                "nulls_first": sorting.nulls_first,
+                            })
+                        })
+                        .collect();
+                    api::result_json(context, json!(sorting_columns))?;
+                }
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.row_group_idx as i64)
+    }
+}
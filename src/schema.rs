@@ -0,0 +1,284 @@
+use parquet::{
+    basic::{ConvertedType, LogicalType, Repetition, TimeUnit, Type as PhysicalType},
+    schema::types::{Type, TypePtr},
+};
+use sqlite_loadable::{Error, Result};
+
+use crate::options::{DecimalMode, NestedMode, ReadOptions, TimestampMode};
+use crate::timestamps::{time_unit, TimestampType};
+
+/// A column of a `parquet` table, read from each file by name.
+#[derive(Debug, Clone)]
+pub struct TableColumn {
+    pub name: String,
+    /// Names of the fields from the top of the file schema down to the
+    /// column, more than one only for struct leaves with `nested=flatten`
+    pub path: Vec<String>,
+    /// The column's type, widened to fit every file that has it
+    pub field: TypePtr,
+    /// Whether some file has NULLs in it, or doesn't have it at all
+    pub nullable: bool,
+}
+
+impl TableColumn {
+    fn new(field: &TypePtr) -> TableColumn {
+        TableColumn {
+            name: field.name().to_owned(),
+            path: vec![field.name().to_owned()],
+            field: field.clone(),
+            nullable: field.get_basic_info().repetition() != Repetition::REQUIRED,
+        }
+    }
+
+    /// The columns of a top-level field: itself, or with `nested=flatten`
+    /// every leaf of a struct, named by its path like `address.city`.
+    fn of_field(field: &TypePtr, nested: NestedMode) -> Vec<TableColumn> {
+        if nested == NestedMode::Json || !is_struct(field) {
+            return vec![TableColumn::new(field)];
+        }
+        let optional = field.get_basic_info().repetition() != Repetition::REQUIRED;
+        field
+            .get_fields()
+            .iter()
+            .flat_map(|child| TableColumn::of_field(child, nested))
+            .map(|mut column| {
+                column.name = format!("{}.{}", field.name(), column.name);
+                column.path.insert(0, field.name().to_owned());
+                column.nullable |= optional;
+                column
+            })
+            .collect()
+    }
+}
+
+/// Whether a field is a struct, a group that isn't a list or a map.
+fn is_struct(field: &Type) -> bool {
+    let info = field.get_basic_info();
+    field.is_group()
+        && info.repetition() != Repetition::REPEATED
+        && info.converted_type() == ConvertedType::NONE
+        && info.logical_type().is_none()
+}
+
+/// Finds the field at the end of `path` in a file schema.
+pub fn find_field<'a>(schema: &'a Type, path: &[String]) -> Option<&'a TypePtr> {
+    let (name, rest) = path.split_first()?;
+    let field = schema
+        .get_fields()
+        .iter()
+        .find(|field| field.name() == name)?;
+    match rest {
+        [] => Some(field),
+        _ if field.is_group() => find_field(field, rest),
+        _ => None,
+    }
+}
+
+/// The type a column is declared with in the table's `create table`,
+/// for what its values read as. `DATE`, `TIMESTAMP`, `JSON` and
+/// `DECIMAL(p,s)` are hints for tools, the values themselves are text or
+/// integers.
+pub fn declared_type(field: &Type, options: &ReadOptions) -> String {
+    if field.is_group() {
+        return "JSON".to_owned();
+    }
+    if TimestampType::of(field).is_some() {
+        return match options.timestamps {
+            TimestampMode::Iso8601 => "TIMESTAMP",
+            TimestampMode::UnixEpoch | TimestampMode::UnixEpochMs => "INTEGER",
+            TimestampMode::JulianDay => "REAL",
+        }
+        .to_owned();
+    }
+    if field.get_basic_info().converted_type() == ConvertedType::DECIMAL {
+        return match options.decimal {
+            DecimalMode::Text => {
+                format!("DECIMAL({},{})", field.get_precision(), field.get_scale())
+            }
+            DecimalMode::Real => "REAL".to_owned(),
+            DecimalMode::IntScaled => "INTEGER".to_owned(),
+        };
+    }
+    if time_unit(field).is_some() {
+        return "TIME".to_owned();
+    }
+    leaf_declared_type(field).to_owned()
+}
+
+fn leaf_declared_type(field: &Type) -> &'static str {
+    match field.get_basic_info().converted_type() {
+        ConvertedType::UTF8 | ConvertedType::ENUM => return "TEXT",
+        ConvertedType::JSON | ConvertedType::BSON => return "JSON",
+        ConvertedType::DATE => return "DATE",
+        ConvertedType::INTERVAL => return "TEXT",
+        _ => (),
+    }
+    if field.get_basic_info().logical_type() == Some(LogicalType::Uuid) {
+        return "TEXT";
+    }
+    match field.get_physical_type() {
+        PhysicalType::BOOLEAN | PhysicalType::INT32 | PhysicalType::INT64 => "INTEGER",
+        PhysicalType::INT96 => "TIMESTAMP",
+        PhysicalType::FLOAT | PhysicalType::DOUBLE => "REAL",
+        PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => "BLOB",
+    }
+}
+
+/// Names a logical type the way the file's printed schema does, ex
+/// `TIMESTAMP(MICROS,true)` or `DECIMAL(9,2)`.
+pub fn logical_type_name(logical_type: &LogicalType) -> String {
+    let unit_name = |unit: &TimeUnit| match unit {
+        TimeUnit::MILLIS(_) => "MILLIS",
+        TimeUnit::MICROS(_) => "MICROS",
+        TimeUnit::NANOS(_) => "NANOS",
+    };
+    match logical_type {
+        LogicalType::Integer {
+            bit_width,
+            is_signed,
+        } => format!("INTEGER({},{})", bit_width, is_signed),
+        LogicalType::Decimal { scale, precision } => format!("DECIMAL({},{})", precision, scale),
+        LogicalType::Timestamp {
+            is_adjusted_to_u_t_c,
+            unit,
+        } => format!("TIMESTAMP({},{})", unit_name(unit), is_adjusted_to_u_t_c),
+        LogicalType::Time {
+            is_adjusted_to_u_t_c,
+            unit,
+        } => format!("TIME({},{})", unit_name(unit), is_adjusted_to_u_t_c),
+        LogicalType::Date => "DATE".to_owned(),
+        LogicalType::Bson => "BSON".to_owned(),
+        LogicalType::Json => "JSON".to_owned(),
+        LogicalType::String => "STRING".to_owned(),
+        LogicalType::Uuid => "UUID".to_owned(),
+        LogicalType::Enum => "ENUM".to_owned(),
+        LogicalType::List => "LIST".to_owned(),
+        LogicalType::Map => "MAP".to_owned(),
+        LogicalType::Unknown => "UNKNOWN".to_owned(),
+    }
+}
+
+/// The table columns of a single file, or of files that all have the
+/// exact same columns.
+pub fn file_columns(fields: &[TypePtr], nested: NestedMode) -> Vec<TableColumn> {
+    fields
+        .iter()
+        .flat_map(|field| TableColumn::of_field(field, nested))
+        .collect()
+}
+
+/// Describes a type for error messages, ex `INT32 (INT_16)`.
+fn describe(field: &Type) -> String {
+    if field.is_group() {
+        return "a group".to_owned();
+    }
+    match field.get_basic_info().converted_type() {
+        ConvertedType::NONE => field.get_physical_type().to_string(),
+        converted => format!("{} ({})", field.get_physical_type(), converted),
+    }
+}
+
+/// Whether two types hold the same kind of values, no matter whether
+/// they're optional or required.
+fn same_values(a: &Type, b: &Type) -> bool {
+    let (a_info, b_info) = (a.get_basic_info(), b.get_basic_info());
+    if a_info.converted_type() != b_info.converted_type()
+        || a_info.logical_type() != b_info.logical_type()
+    {
+        return false;
+    }
+    match (a.is_primitive(), b.is_primitive()) {
+        (true, true) => {
+            a.get_physical_type() == b.get_physical_type()
+                && a.get_precision() == b.get_precision()
+                && a.get_scale() == b.get_scale()
+        }
+        (false, false) => a.get_fields() == b.get_fields(),
+        _ => false,
+    }
+}
+
+/// Whether every value of `narrow` fits losslessly in `wide`: INT32 in
+/// INT64, and FLOAT in DOUBLE.
+fn widens_to(narrow: &Type, wide: &Type) -> bool {
+    if !narrow.is_primitive() || !wide.is_primitive() {
+        return false;
+    }
+    let narrow_converted = narrow.get_basic_info().converted_type();
+    let wide_converted = wide.get_basic_info().converted_type();
+    match (narrow.get_physical_type(), wide.get_physical_type()) {
+        (PhysicalType::INT32, PhysicalType::INT64) => {
+            matches!(
+                narrow_converted,
+                ConvertedType::NONE
+                    | ConvertedType::INT_8
+                    | ConvertedType::INT_16
+                    | ConvertedType::INT_32
+                    | ConvertedType::UINT_8
+                    | ConvertedType::UINT_16
+                    | ConvertedType::UINT_32
+            ) && matches!(wide_converted, ConvertedType::NONE | ConvertedType::INT_64)
+        }
+        (PhysicalType::FLOAT, PhysicalType::DOUBLE) => {
+            narrow_converted == ConvertedType::NONE && wide_converted == ConvertedType::NONE
+        }
+        _ => false,
+    }
+}
+
+/// Merges the columns of every file by name, for `union_by_name=1`.
+/// Columns keep the order they're first seen in, and types widen to fit
+/// every file. `schemas` pairs each file's path with its top-level fields.
+pub fn union_columns(
+    schemas: &[(&str, &[TypePtr])],
+    nested: NestedMode,
+) -> Result<Vec<TableColumn>> {
+    let mut columns: Vec<TableColumn> = vec![];
+    // the file each column's current type comes from, for error messages
+    let mut origins: Vec<&str> = vec![];
+    for (file, (path, fields)) in schemas.iter().enumerate() {
+        let file_columns = file_columns(fields, nested);
+        for file_column in &file_columns {
+            let field = &file_column.field;
+            let i = match columns.iter().position(|c| c.name == file_column.name) {
+                Some(i) => i,
+                None => {
+                    let mut column = file_column.clone();
+                    // files before this one don't have it
+                    column.nullable |= file > 0;
+                    columns.push(column);
+                    origins.push(path);
+                    continue;
+                }
+            };
+            let column = &mut columns[i];
+            column.nullable |= file_column.nullable;
+            if same_values(&column.field, field) || widens_to(field, &column.field) {
+                continue;
+            }
+            if widens_to(&column.field, field) {
+                column.field = field.clone();
+                origins[i] = path;
+                continue;
+            }
+            return Err(Error::new_message(
+                format!(
+                    "parquet file '{}' has column '{}' as {}, which doesn't fit {} from '{}'",
+                    path,
+                    file_column.name,
+                    describe(field),
+                    describe(&column.field),
+                    origins[i],
+                )
+                .as_str(),
+            ));
+        }
+        // columns this file doesn't have
+        for column in columns.iter_mut() {
+            if !file_columns.iter().any(|c| c.name == column.name) {
+                column.nullable = true;
+            }
+        }
+    }
+    Ok(columns)
+}
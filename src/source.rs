@@ -0,0 +1,170 @@
+use parquet::{
+    errors::ParquetError,
+    file::{
+        footer::decode_footer,
+        reader::{ChunkReader, FileReader},
+        FOOTER_SIZE,
+    },
+    format::FileMetaData,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api::{self, ValueType},
+    table::{ConstraintOperator, IndexInfo},
+    BestIndexError, Error, Result,
+};
+
+use std::{fs::File, os::raw::c_int, rc::Rc};
+
+use bytes::Bytes;
+
+use crate::errors::parquet_error;
+use crate::footer::decode_file_metadata;
+use crate::parquet::{open_blob, open_file};
+use crate::trace::Trace;
+
+/// Stands in for the path of a file read from a blob, in errors.
+const BLOB_NAME: &str = "<blob>";
+
+/// The `source` argument of the table functions: the path to a parquet
+/// file, or the file itself as a blob, like from `readfile()`.
+pub enum Source {
+    Path(String),
+    Blob(Bytes),
+}
+
+impl Source {
+    pub fn from_value(value: Option<&*mut sqlite3_value>) -> Result<Source> {
+        match value {
+            Some(value) if api::value_type(value) == ValueType::Blob => {
+                Ok(Source::Blob(Bytes::copy_from_slice(api::value_blob(value))))
+            }
+            Some(value) => Ok(Source::Path(api::value_text(value)?.to_owned())),
+            None => Err(Error::new_message("parquet file path is required")),
+        }
+    }
+
+    /// The file's path, or a stand-in for a blob, for errors.
+    pub fn name(&self) -> &str {
+        match self {
+            Source::Path(path) => path,
+            Source::Blob(_) => BLOB_NAME,
+        }
+    }
+
+    pub fn open(&self, trace: &Trace) -> Result<Box<dyn FileReader>> {
+        Ok(match self {
+            Source::Path(path) => Box::new(open_file(path, trace)?),
+            // the reader shares the blob, it isn't copied
+            Source::Blob(blob) => Box::new(open_blob(blob.clone(), BLOB_NAME, trace)?),
+        })
+    }
+
+    /// The thrift encoded metadata from the end of the file.
+    pub fn footer(&self) -> Result<Bytes> {
+        let name = self.name();
+        match self {
+            Source::Path(path) => read_footer(&open_path(path)?, name),
+            Source::Blob(blob) => read_footer(blob, name),
+        }
+    }
+
+    /// The file's metadata as it's stored, with the fields the parquet
+    /// crate drops when it decodes it, like row group ordinals.
+    pub fn file_metadata(&self) -> Result<FileMetaData> {
+        decode_file_metadata(&self.footer()?).map_err(|err| self.footer_error(err))
+    }
+
+    /// An error decoding the footer.
+    pub fn footer_error(&self, error: thrift::Error) -> Error {
+        parquet_error(
+            self.name(),
+            ParquetError::General(format!("could not decode metadata: {}", error)),
+        )
+    }
+
+    /// Returns the source as it was given, for the hidden `source` column.
+    pub fn result(&self, context: *mut sqlite3_context) -> Result<()> {
+        match self {
+            Source::Path(path) => api::result_text(context, path)?,
+            Source::Blob(blob) => api::result_blob(context, blob),
+        }
+        Ok(())
+    }
+}
+
+/// The trace a table function is registered with, to hand its cursors.
+pub fn trace_aux(aux: Option<&Rc<Trace>>) -> Result<Rc<Trace>> {
+    aux.cloned()
+        .ok_or_else(|| Error::new_message("missing trace"))
+}
+
+/// Plans a scan of a table function that takes `source` and maybe more
+/// arguments, in the hidden columns `arguments`. The first `required` of
+/// them must be given, and each is passed to xFilter in that order.
+pub fn source_best_index(
+    mut info: IndexInfo,
+    arguments: &[c_int],
+    required: usize,
+) -> core::result::Result<(), BestIndexError> {
+    let mut given = vec![false; arguments.len()];
+    for mut constraint in info.constraints() {
+        // constraints on other columns are checked by SQLite
+        let argument = match arguments
+            .iter()
+            .position(|column| *column == constraint.column_idx())
+        {
+            Some(argument) => argument,
+            None => continue,
+        };
+        if constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ) {
+            constraint.set_omit(true);
+            constraint.set_argv_index(argument as c_int + 1);
+            given[argument] = true;
+        } else {
+            return Err(BestIndexError::Constraint);
+        }
+    }
+    if given[..required].contains(&false) {
+        return Err(BestIndexError::Error);
+    }
+    info.set_estimated_cost(100000.0);
+    info.set_estimated_rows(100000);
+    info.set_idxnum(1);
+
+    Ok(())
+}
+
+/// Opens the file of a path source, for reading it without a `FileReader`.
+pub fn open_path(path: &str) -> Result<File> {
+    File::open(path).map_err(|err| {
+        Error::new_message(format!("could not open parquet file '{}': {}", path, err).as_str())
+    })
+}
+
+/// Reads the thrift footer at the end of a parquet file.
+pub fn read_footer<R: ChunkReader>(chunks: &R, name: &str) -> Result<Bytes> {
+    let error = |err| parquet_error(name, err);
+    let len = chunks.len();
+    if len < FOOTER_SIZE as u64 {
+        return Err(error(ParquetError::General(
+            "file is smaller than the footer".to_owned(),
+        )));
+    }
+    let mut footer = [0; FOOTER_SIZE];
+    footer.copy_from_slice(
+        &chunks
+            .get_bytes(len - FOOTER_SIZE as u64, FOOTER_SIZE)
+            .map_err(error)?,
+    );
+    let metadata_len = decode_footer(&footer).map_err(error)?;
+    if (metadata_len + FOOTER_SIZE) as u64 > len {
+        return Err(error(ParquetError::General(format!(
+            "metadata is {} bytes, but the file is only {}",
+            metadata_len, len
+        ))));
+    }
+    chunks
+        .get_bytes(len - (metadata_len + FOOTER_SIZE) as u64, metadata_len)
+        .map_err(error)
+}
@@ -0,0 +1,67 @@
+use parquet::{
+    basic::{ConvertedType, Type as PhysicalType},
+    file::statistics::Statistics,
+    schema::types::ColumnDescPtr,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Result};
+
+use crate::options::ReadOptions;
+use crate::parquet::result_leaf;
+use crate::raw::RawValue;
+
+/// The min and max of column chunk or page stats, when they have them.
+pub fn stats_bounds(stats: &Statistics) -> Option<(RawValue, RawValue)> {
+    if !stats.has_min_max_set() {
+        return None;
+    }
+    Some(match stats {
+        Statistics::Boolean(stats) => (RawValue::Bool(*stats.min()), RawValue::Bool(*stats.max())),
+        Statistics::Int32(stats) => (RawValue::Int32(*stats.min()), RawValue::Int32(*stats.max())),
+        Statistics::Int64(stats) => (RawValue::Int64(*stats.min()), RawValue::Int64(*stats.max())),
+        Statistics::Int96(stats) => (RawValue::Int96(*stats.min()), RawValue::Int96(*stats.max())),
+        Statistics::Float(stats) => (RawValue::Float(*stats.min()), RawValue::Float(*stats.max())),
+        Statistics::Double(stats) => (
+            RawValue::Double(*stats.min()),
+            RawValue::Double(*stats.max()),
+        ),
+        Statistics::ByteArray(stats) => (
+            RawValue::Bytes(stats.min().clone()),
+            RawValue::Bytes(stats.max().clone()),
+        ),
+        Statistics::FixedLenByteArray(stats) => (
+            RawValue::Bytes(stats.min().clone().into()),
+            RawValue::Bytes(stats.max().clone().into()),
+        ),
+    })
+}
+
+/// Whether a column's values are read as text, which its stats can't be
+/// when a writer truncated them mid-character.
+fn is_text(descr: &ColumnDescPtr) -> bool {
+    descr.physical_type() == PhysicalType::BYTE_ARRAY
+        && matches!(
+            descr.converted_type(),
+            ConvertedType::UTF8 | ConvertedType::ENUM | ConvertedType::JSON
+        )
+}
+
+/// Returns a stats value like the `parquet` table returns the column's
+/// values, or as a blob when it's text that isn't valid UTF-8. `path` is
+/// the file's, for errors.
+pub fn result_stat(
+    context: *mut sqlite3_context,
+    descr: &ColumnDescPtr,
+    value: RawValue,
+    options: &ReadOptions,
+    path: &str,
+) -> Result<()> {
+    if let RawValue::Bytes(bytes) = &value {
+        if is_text(descr) && bytes.as_utf8().is_err() {
+            api::result_blob(context, bytes.data());
+            return Ok(());
+        }
+    }
+    let name = descr.path().string();
+    result_leaf(context, descr, value, options, path, &name)
+}
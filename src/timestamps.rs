@@ -0,0 +1,183 @@
+use chrono::NaiveDateTime;
+use parquet::{
+    basic::{ConvertedType, LogicalType, TimeUnit, Type as PhysicalType},
+    data_type::Int96,
+    schema::types::Type,
+};
+
+use crate::options::TimestampMode;
+use crate::pruning::Scalar;
+
+/// Julian day number of 1970-01-01 00:00:00.
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2440587.5;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Julian day number of the day starting at 1970-01-01 00:00:00, as INT96
+/// timestamps count days.
+const UNIX_EPOCH_JULIAN_DAY_NUMBER: i64 = 2440588;
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// INT96 timestamps, from older Spark and Impala writers, are local times
+/// in nanoseconds.
+pub const INT96_TIMESTAMP: TimestampType = TimestampType {
+    unit: Unit::Nanos,
+    utc: false,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl Unit {
+    fn per_second(&self) -> i64 {
+        match self {
+            Unit::Millis => 1_000,
+            Unit::Micros => 1_000_000,
+            Unit::Nanos => 1_000_000_000,
+        }
+    }
+
+    /// Digits after the point, enough for every value of the unit.
+    fn digits(&self) -> usize {
+        match self {
+            Unit::Millis => 3,
+            Unit::Micros => 6,
+            Unit::Nanos => 9,
+        }
+    }
+}
+
+/// How a timestamp column's values are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampType {
+    pub unit: Unit,
+    /// Whether values are instants in UTC, rather than local times in an
+    /// unknown time zone
+    pub utc: bool,
+}
+
+impl TimestampType {
+    /// The timestamp type of a primitive column, from its logical type or,
+    /// in older files, its converted type.
+    pub fn of(field: &Type) -> Option<TimestampType> {
+        if !field.is_primitive() {
+            return None;
+        }
+        let info = field.get_basic_info();
+        if let Some(LogicalType::Timestamp {
+            is_adjusted_to_u_t_c,
+            unit,
+        }) = info.logical_type()
+        {
+            let unit = match unit {
+                TimeUnit::MILLIS(_) => Unit::Millis,
+                TimeUnit::MICROS(_) => Unit::Micros,
+                TimeUnit::NANOS(_) => Unit::Nanos,
+            };
+            return Some(TimestampType {
+                unit,
+                utc: is_adjusted_to_u_t_c,
+            });
+        }
+        // the converted types are always adjusted to UTC
+        match info.converted_type() {
+            ConvertedType::TIMESTAMP_MILLIS => Some(TimestampType {
+                unit: Unit::Millis,
+                utc: true,
+            }),
+            ConvertedType::TIMESTAMP_MICROS => Some(TimestampType {
+                unit: Unit::Micros,
+                utc: true,
+            }),
+            _ if field.get_physical_type() == PhysicalType::INT96 => Some(INT96_TIMESTAMP),
+            _ => None,
+        }
+    }
+}
+
+/// The value the `parquet` table returns for a timestamp, in the form
+/// `timestamps=` picks, or `None` when it's past what chrono can format.
+pub fn timestamp_scalar(
+    value: i64,
+    timestamp: TimestampType,
+    mode: TimestampMode,
+) -> Option<Scalar> {
+    let per_second = timestamp.unit.per_second();
+    let seconds = value.div_euclid(per_second);
+    let fraction = value.rem_euclid(per_second);
+    match mode {
+        TimestampMode::Iso8601 => {
+            let nanos = fraction * (1_000_000_000 / per_second);
+            let datetime = NaiveDateTime::from_timestamp_opt(seconds, u32::try_from(nanos).ok()?)?;
+            Some(Scalar::Text(format!(
+                "{}.{:0width$}{}",
+                datetime.format("%Y-%m-%d %H:%M:%S"),
+                fraction,
+                if timestamp.utc { "Z" } else { "" },
+                width = timestamp.unit.digits()
+            )))
+        }
+        TimestampMode::UnixEpoch => Some(Scalar::Integer(seconds.into())),
+        TimestampMode::UnixEpochMs => {
+            Some(Scalar::Integer(value.div_euclid(per_second / 1000).into()))
+        }
+        TimestampMode::JulianDay => Some(Scalar::Real(
+            UNIX_EPOCH_JULIAN_DAY
+                + (seconds as f64 + fraction as f64 / per_second as f64) / SECONDS_PER_DAY,
+        )),
+    }
+}
+
+/// The unit of a TIME column, from its logical type or, in older files,
+/// its converted type.
+pub fn time_unit(field: &Type) -> Option<Unit> {
+    if !field.is_primitive() {
+        return None;
+    }
+    let info = field.get_basic_info();
+    if let Some(LogicalType::Time { unit, .. }) = info.logical_type() {
+        return Some(match unit {
+            TimeUnit::MILLIS(_) => Unit::Millis,
+            TimeUnit::MICROS(_) => Unit::Micros,
+            TimeUnit::NANOS(_) => Unit::Nanos,
+        });
+    }
+    match info.converted_type() {
+        ConvertedType::TIME_MILLIS => Some(Unit::Millis),
+        ConvertedType::TIME_MICROS => Some(Unit::Micros),
+        _ => None,
+    }
+}
+
+/// Formats a TIME of day as `HH:MM:SS.fff`, with as many digits after the
+/// point as its unit has, or `None` when it isn't inside a day.
+pub fn format_time(value: i64, unit: Unit) -> Option<String> {
+    let per_second = unit.per_second();
+    if !(0..86400 * per_second).contains(&value) {
+        return None;
+    }
+    let seconds = value / per_second;
+    Some(format!(
+        "{:02}:{:02}:{:02}.{:0width$}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        value % per_second,
+        width = unit.digits()
+    ))
+}
+
+/// Nanoseconds since 1970-01-01 of an INT96 timestamp, which holds
+/// nanoseconds of the day in its first 8 bytes and a Julian day number in
+/// its last 4. `None` when it's past what an i64 holds.
+pub fn int96_nanos(value: &Int96) -> Option<i64> {
+    let data = value.data();
+    let nanos_of_day = (i64::from(data[1]) << 32) | i64::from(data[0]);
+    let days = i64::from(data[2]) - UNIX_EPOCH_JULIAN_DAY_NUMBER;
+    days.checked_mul(NANOS_PER_DAY)?.checked_add(nanos_of_day)
+}
@@ -0,0 +1,344 @@
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{IndexInfo, VTab, VTabArguments, VTabCursor},
+    BestIndexError, Error, Result,
+};
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    mem,
+    os::raw::c_int,
+    rc::Rc,
+    time::Duration,
+};
+
+use chrono::Utc;
+
+/// Environment variable that sets the trace level when the extension loads.
+static TRACE_ENV: &str = "SQLITE_PARQUET_TRACE";
+
+/// Older events are dropped once the log holds this many.
+const MAX_EVENTS: usize = 10000;
+
+/// How much detail gets traced. Each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    /// Files opened and row groups pruned, once per scan
+    Info,
+    /// Per row group reads and decode times
+    Debug,
+}
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Level> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "off" | "0" | "" => Some(Level::Off),
+            "info" | "1" => Some(Level::Info),
+            "debug" | "2" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// A traced event, a row of `parquet_trace_log`.
+pub struct Event {
+    pub id: i64,
+    pub time: String,
+    pub level: Level,
+    pub event: &'static str,
+    pub path: String,
+    pub row_group: Option<usize>,
+    pub bytes: Option<i64>,
+    pub elapsed: Option<Duration>,
+    pub message: Option<String>,
+}
+
+/// Trace events recorded for one database connection. Nothing is recorded
+/// until the level is raised, with `parquet_trace()` or SQLITE_PARQUET_TRACE.
+pub struct Trace {
+    level: Cell<Level>,
+    next_id: Cell<i64>,
+    events: RefCell<VecDeque<Event>>,
+}
+
+impl Trace {
+    pub fn from_env() -> Trace {
+        let level = std::env::var(TRACE_ENV)
+            .ok()
+            .and_then(|level| Level::from_name(&level))
+            .unwrap_or(Level::Off);
+        Trace {
+            level: Cell::new(level),
+            next_id: Cell::new(1),
+            events: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level != Level::Off && level <= self.level.get()
+    }
+
+    /// Starts an event at `level`, which is recorded once `finish()` is
+    /// called on it, if that level is being traced.
+    pub fn event(&self, level: Level, event: &'static str, path: &str) -> EventBuilder<'_> {
+        EventBuilder {
+            trace: self,
+            event: self.enabled(level).then(|| Event {
+                id: 0,
+                time: Utc::now().format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+                level,
+                event,
+                path: path.to_owned(),
+                row_group: None,
+                bytes: None,
+                elapsed: None,
+                message: None,
+            }),
+        }
+    }
+
+    fn record(&self, mut event: Event) {
+        event.id = self.next_id.get();
+        self.next_id.set(event.id + 1);
+        let mut events = self.events.borrow_mut();
+        if events.len() >= MAX_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+pub struct EventBuilder<'a> {
+    trace: &'a Trace,
+    event: Option<Event>,
+}
+
+impl EventBuilder<'_> {
+    pub fn row_group(mut self, row_group: usize) -> Self {
+        if let Some(event) = self.event.as_mut() {
+            event.row_group = Some(row_group);
+        }
+        self
+    }
+
+    pub fn bytes(mut self, bytes: i64) -> Self {
+        if let Some(event) = self.event.as_mut() {
+            event.bytes = Some(bytes);
+        }
+        self
+    }
+
+    pub fn elapsed(mut self, elapsed: Duration) -> Self {
+        if let Some(event) = self.event.as_mut() {
+            event.elapsed = Some(elapsed);
+        }
+        self
+    }
+
+    /// The message is only built when the event is being traced.
+    pub fn message<F: FnOnce() -> String>(mut self, message: F) -> Self {
+        if let Some(event) = self.event.as_mut() {
+            event.message = Some(message());
+        }
+        self
+    }
+
+    pub fn finish(self) {
+        if let Some(event) = self.event {
+            self.trace.record(event);
+        }
+    }
+}
+
+/// `parquet_trace()` returns the current trace level, and
+/// `parquet_trace(level)` sets it to 'off', 'info' or 'debug' and returns
+/// the new level. Events already traced stay in `parquet_trace_log`.
+pub fn parquet_trace(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+    trace: &Rc<Trace>,
+) -> Result<()> {
+    if values.len() > 1 {
+        return Err(Error::new_message("parquet_trace takes at most 1 argument"));
+    }
+    if let Some(value) = values.first() {
+        let name = api::value_text(value)?;
+        let level = Level::from_name(name).ok_or_else(|| {
+            Error::new_message(
+                format!(
+                    "unknown trace level '{}', expected 'off', 'info' or 'debug'",
+                    name
+                )
+                .as_str(),
+            )
+        })?;
+        trace.level.set(level);
+    }
+    api::result_text(context, trace.level.get().name())?;
+    Ok(())
+}
+
+static CREATE_SQL: &str = "CREATE TABLE x(
+    time text,
+    level text,
+    event text,
+    path text,
+    row_group integer,
+    bytes integer,
+    elapsed_us integer,
+    message text
+  )";
+enum Columns {
+    Time,
+    Level,
+    Event,
+    Path,
+    RowGroup,
+    Bytes,
+    ElapsedUs,
+    Message,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Time),
+        1 => Some(Columns::Level),
+        2 => Some(Columns::Event),
+        3 => Some(Columns::Path),
+        4 => Some(Columns::RowGroup),
+        5 => Some(Columns::Bytes),
+        6 => Some(Columns::ElapsedUs),
+        7 => Some(Columns::Message),
+        _ => None,
+    }
+}
+
+#[repr(C)]
+pub struct TraceLogTable {
+    /// must be first
+    base: sqlite3_vtab,
+    trace: Rc<Trace>,
+}
+
+impl<'vtab> VTab<'vtab> for TraceLogTable {
+    type Aux = Rc<Trace>;
+    type Cursor = TraceLogCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, TraceLogTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let trace = aux
+            .cloned()
+            .ok_or_else(|| Error::new_message("parquet_trace_log is missing its trace"))?;
+        let vtab = TraceLogTable { base, trace };
+        Ok((CREATE_SQL.to_owned(), vtab))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        info.set_estimated_cost(100000.0);
+        info.set_estimated_rows(100000);
+        info.set_idxnum(1);
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<TraceLogCursor> {
+        Ok(TraceLogCursor::new(self.trace.clone()))
+    }
+}
+
+#[repr(C)]
+pub struct TraceLogCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    trace: Rc<Trace>,
+    /// Position in the log, which drops old events once it's full
+    idx: usize,
+}
+impl TraceLogCursor {
+    fn new(trace: Rc<Trace>) -> TraceLogCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        TraceLogCursor {
+            base,
+            trace,
+            idx: 0,
+        }
+    }
+}
+
+impl VTabCursor for TraceLogCursor {
+    fn filter(
+        &mut self,
+        _idx_num: c_int,
+        _idx_str: Option<&str>,
+        _values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        self.idx = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.idx += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.idx >= self.trace.events.borrow().len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let events = self.trace.events.borrow();
+        let event = match events.get(self.idx) {
+            Some(event) => event,
+            None => return Ok(()),
+        };
+        match column(i) {
+            Some(Columns::Time) => api::result_text(context, &event.time)?,
+            Some(Columns::Level) => api::result_text(context, event.level.name())?,
+            Some(Columns::Event) => api::result_text(context, event.event)?,
+            Some(Columns::Path) => api::result_text(context, &event.path)?,
+            Some(Columns::RowGroup) => {
+                if let Some(row_group) = event.row_group {
+                    api::result_int64(context, row_group as i64);
+                }
+            }
+            Some(Columns::Bytes) => {
+                if let Some(bytes) = event.bytes {
+                    api::result_int64(context, bytes);
+                }
+            }
+            Some(Columns::ElapsedUs) => {
+                if let Some(elapsed) = event.elapsed {
+                    api::result_int64(context, elapsed.as_micros() as i64);
+                }
+            }
+            Some(Columns::Message) => {
+                if let Some(message) = &event.message {
+                    api::result_text(context, message)?;
+                }
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        let events = self.trace.events.borrow();
+        Ok(events.get(self.idx).map_or(0, |event| event.id))
+    }
+}
@@ -1,3 +1,4 @@
+import os
 import pandas as pd
 from datetime import datetime
 from zoneinfo import ZoneInfo
@@ -38,7 +39,199 @@ pd.DataFrame(data={
     ]),
 }).to_parquet('tests/data/dates.parquet')
 
+pd.DataFrame(data={
+  'id': range(100),
+  'name': [f"name{i:03}" for i in range(100)],
+  'value': [None if 20 <= i < 30 or i % 7 == 0 else i / 2 for i in range(100)],
+}).to_parquet('tests/data/row_groups.parquet', row_group_size=10)
+
+# a dataset split over several files
+os.makedirs('tests/data/parts', exist_ok=True)
+for part in range(3):
+  ids = range(part * 10, part * 10 + 10)
+  pd.DataFrame(data={
+    'id': ids,
+    'name': [f"name{i:03}" for i in ids],
+  }).to_parquet(f'tests/data/parts/part-{part}.parquet', row_group_size=5)
+
+# hive partitioned datasets
+hive = [
+  ('2022-10-25', 'us', 0),
+  ('2022-10-25', 'eu', 10),
+  ('2022-10-26', 'us', 20),
+  ('2022-10-26', 'a%2Fb', 30),
+  ('__HIVE_DEFAULT_PARTITION__', 'us', 90),
+]
+for i, (date, region, start) in enumerate(hive):
+  directory = f'tests/data/hive/date={date}/region={region}'
+  os.makedirs(directory, exist_ok=True)
+  ids = [90] if start == 90 else range(start, start + 5)
+  pd.DataFrame(data={'id': ids}).to_parquet(f'{directory}/part-{9 if start == 90 else i}.parquet')
+
+for year in [2021, 2022]:
+  os.makedirs(f'tests/data/years/year={year}', exist_ok=True)
+  pd.DataFrame(data={'id': [year]}).to_parquet(f'tests/data/years/year={year}/data.parquet')
+
+# files whose schemas don't line up
+os.makedirs('tests/data/mismatch', exist_ok=True)
+pd.DataFrame(data={'id': [1]}).to_parquet('tests/data/mismatch/a.parquet')
+pd.DataFrame(data={'id': ["1"]}).to_parquet('tests/data/mismatch/b.parquet')
+
+# files whose columns drift over time, for union_by_name=1
+os.makedirs('tests/data/union', exist_ok=True)
+pd.DataFrame(data={
+  'id': pd.Series([1, 2], dtype='int32'),
+  'name': ['alex', 'brian'],
+  'score': pd.Series([1.5, 2.5], dtype='float32'),
+}).to_parquet('tests/data/union/a.parquet')
+pd.DataFrame(data={
+  'value': [10.0, None],
+  'id': pd.Series([3, 4], dtype='int64'),
+  'score': pd.Series([3.25, 4.25], dtype='float64'),
+}).to_parquet('tests/data/union/b.parquet')
+
+# decimals stored as INT32, INT64 and FIXED_LEN_BYTE_ARRAY
+from decimal import Decimal
+import pyarrow as pa
+import pyarrow.parquet as pq
+pq.write_table(pa.table({
+  'd32': pa.array([Decimal('123.45'), Decimal('-0.05'), Decimal('0.00')], pa.decimal128(9, 2)),
+  'd64': pa.array([Decimal('123456789.0123'), Decimal('-0.0001'), Decimal('5.0000')], pa.decimal128(18, 4)),
+  'dfixed': pa.array([
+    Decimal('1234567890123456789012345678.9012345678'),
+    Decimal('-0.0000000001'),
+    None,
+  ], pa.decimal128(38, 10)),
+}), 'tests/data/decimals.parquet', store_decimal_as_integer=True)
+
+# timestamps in every unit, with and without UTC adjustment
+instants = [1666701296789123456, -500000000, None]
+pq.write_table(pa.table({
+  'ts_millis': pa.array([i and i // 10**6 for i in instants], pa.timestamp('ms', tz='UTC')),
+  'ts_micros': pa.array([i and i // 10**3 for i in instants], pa.timestamp('us')),
+  'ts_nanos': pa.array(instants, pa.timestamp('ns', tz='UTC')),
+}), 'tests/data/timestamps.parquet', version='2.6')
+
+# legacy INT96 timestamps, like older Spark and Impala write
+pq.write_table(pa.table({
+  'id': pa.array([1, 2, 3], pa.int64()),
+  'ts': pa.array(instants, pa.timestamp('ns')),
+}, schema=pa.schema([pa.field('id', pa.int64(), nullable=False), pa.field('ts', pa.timestamp('ns'))])),
+  'tests/data/int96.parquet', use_deprecated_int96_timestamps=True)
+
+# logical types pyarrow can't all write: TIME, INTERVAL, UUID, ENUM, JSON and
+# BSON. logical.parquet was written with parquet-rs's SerializedFileWriter,
+# one row group, rows 0 and 1 below and a row of NULLs, with the schema
+#
+#   message schema {
+#     optional int32 time_ms (TIME(MILLIS,true));
+#     optional int64 time_us (TIME(MICROS,false));
+#     optional int64 time_ns (TIME(NANOS,false));
+#     optional fixed_len_byte_array(12) duration (INTERVAL);
+#     optional fixed_len_byte_array(16) id (UUID);
+#     optional binary color (ENUM);
+#     optional binary doc (JSON);
+#     optional binary bson (BSON);
+#   }
+#
+# time_ms:  45296789, 0
+# time_us:  45296789123, 86399999999
+# time_ns:  45296789123456, 1
+# duration: (14 months, 3 days, 14706789 ms), (0, 0, 0)
+# id:       f81d4fae-7dec-11d0-a765-00a0c91e6bf6, all zeros
+# color:    'red', 'green'
+# doc:      '{"a":[1,2]}', '[]'
+# bson:     {name: 'alex', n: int32 1, tags: ['x'], when: date 1666701296789, score: 2.5}, {}
+
+# FLOAT16, which needs pyarrow 15 or newer
+import numpy as np
+pq.write_table(pa.table({
+  'id': pa.array([1, 2, 3], pa.int64()),
+  'half': pa.array(np.array([1.5, 0, -0.25], dtype=np.float16), mask=np.array([False, True, False])),
+}, schema=pa.schema([pa.field('id', pa.int64(), nullable=False), pa.field('half', pa.float16())])),
+  'tests/data/float16.parquet')
+
+# corrupt files, which should give errors instead of crashing
+with open('tests/data/not_parquet.parquet', 'w') as f:
+  f.write("id,name\n1,alex\n")
+
+with open('tests/data/row_groups.parquet', 'rb') as f:
+  valid = f.read()
+
+# the footer is cut off
+with open('tests/data/truncated.parquet', 'wb') as f:
+  f.write(valid[:len(valid) // 2])
+
+# the footer is intact, but the pages it points to are garbage
+with open('tests/data/corrupt_pages.parquet', 'wb') as f:
+  f.write(valid[:4] + b'\xff' * 256 + valid[260:])
+
+# structs, with a struct inside and a list next to them, 2 rows per row group
+geo = pa.struct([('lat', pa.float64()), ('lon', pa.float64())])
+pq.write_table(pa.table({
+  'id': pa.array([1, 2, 3, 4], pa.int64()),
+  'address': pa.array([
+    {'city': 'Paris', 'zip': 75001, 'geo': {'lat': 48.86, 'lon': 2.35}},
+    {'city': 'Berlin', 'zip': 10115, 'geo': None},
+    None,
+    {'city': 'Tokyo', 'zip': None, 'geo': {'lat': 35.68, 'lon': 139.69}},
+  ], pa.struct([('city', pa.string()), ('zip', pa.int32()), ('geo', geo)])),
+  'tags': pa.array([['a'], [], None, ['b', 'c']], pa.list_(pa.string())),
+}), 'tests/data/nested.parquet', row_group_size=2)
+
+# lists and maps with NULL, empty and NULL-element entries, 2 rows per row group
+pq.write_table(pa.table({
+  'id': pa.array([1, 2, 3, 4], pa.int64()),
+  'tags': pa.array([['a', None, 'b'], [], None, ['c']], pa.list_(pa.string())),
+  'attrs': pa.array([[('x', 1), ('y', 2)], [], None, [('z', None)]], pa.map_(pa.string(), pa.int64())),
+  'matrix': pa.array([[[1]], [], [], []], pa.list_(pa.list_(pa.int32()))),
+}), 'tests/data/lists.parquet', row_group_size=2)
+
+# UINT64 values past the largest INTEGER
+pq.write_table(pa.table({
+  'id': pa.array([1, 2, 3, 4], pa.int64()),
+  'big': pa.array([1, 2 ** 63, 2 ** 64 - 1, None], pa.uint64()),
+}), 'tests/data/uint64.parquet')
+
+# key/value metadata next to the Arrow schema, with field and schema metadata
+schema = pa.schema([
+  pa.field('id', pa.int64(), nullable=False, metadata={'comment': 'primary key'}),
+  pa.field('at', pa.timestamp('ms', tz='UTC')),
+  pa.field('tags', pa.list_(pa.string())),
+], metadata={'origin': 'fixtures'})
+table = pa.table({'id': [1], 'at': [0], 'tags': [['a']]}, schema=schema)
+table = table.replace_schema_metadata({
+  **table.schema.metadata,
+  'pipeline': 'nightly',
+  'pandas': '{"index_columns": [], "columns": [{"name": "id"}]}',
+})
+pq.write_table(table, 'tests/data/kv.parquet')
+
+# text that isn't valid UTF-8, like a writer that truncated it mid-character
+pq.write_table(pa.table({
+  'id': pa.array([1, 2], pa.int64()),
+  'name': pa.array([b'abc', b'\xff\xfe'], pa.binary()).view(pa.string()),
+}), 'tests/data/invalid_utf8.parquet')
 
+# one row group of 1000 rows in pages of 100 rows, with a page index
+pq.write_table(pa.table({
+  'id': pa.array(range(1000), pa.int64()),
+  'name': pa.array([f'name-{i:04}' for i in range(1000)], pa.string()),
+  'bucket': pa.array([i % 10 for i in range(1000)], pa.int64()),
+  'note': pa.array([None if 500 <= i < 600 else f'note {i // 100}' for i in range(1000)], pa.string()),
+}), 'tests/data/page_index.parquet', data_page_size=1, write_batch_size=100, write_page_index=True)
 
-# breaks
-# pd.Series([1, 2 ** 63], dtype='UInt64'),
\ No newline at end of file
+# 4 row groups of 1000 rows whose min/max all span about the same values,
+# with bloom filters on user_id, account and score. DuckDB writes bloom
+# filters for dictionary encoded columns.
+import duckdb
+duckdb.sql("""
+  copy (
+    select
+      i as id,
+      printf('user-%05d', (i * 7919) % 4001) as user_id,
+      100000 + (i * 7919) % 4001 as account,
+      ((i * 7919) % 4001) / 4.0 as score
+    from range(4000) t(i)
+  ) to 'tests/data/bloom.parquet' (format parquet, row_group_size 1000)
+""")
@@ -1,7 +1,9 @@
 import sqlite3
+import json
 import unittest
 import time
 import os
+import tempfile
 
 EXT_PATH="./target/debug/libparquet0"
 
@@ -32,12 +34,25 @@ def execute_all(sql, args=None):
   return list(map(lambda x: dict(x), results))
 
 FUNCTIONS = [
+  "parquet_bloom_check",
   "parquet_debug",
+  "parquet_trace",
   "parquet_version"
 ]
 
 MODULES = [
   "parquet",
+  "parquet_column_chunks",
+  "parquet_column_index",
+  "parquet_column_values",
+  "parquet_columns",
+  "parquet_each",
+  "parquet_kv_metadata",
+  "parquet_metadata",
+  "parquet_offset_index",
+  "parquet_pages",
+  "parquet_row_groups",
+  "parquet_trace_log",
 ]
 class TestParquet(unittest.TestCase):
   def test_funcs(self):
@@ -56,6 +71,27 @@ class TestParquet(unittest.TestCase):
     debug = db.execute("select parquet_debug()").fetchone()[0]
     self.assertEqual(len(debug.splitlines()), 2)
 
+  def test_parquet_bloom_check(self):
+    bloom_check = lambda column, value: db.execute("select parquet_bloom_check('tests/data/bloom.parquet', ?, ?)", [column, value]).fetchone()[0]
+    self.assertEqual(bloom_check('user_id', 'user-01234'), 1)
+    self.assertEqual(bloom_check('user_id', 'user-01234x'), 0)
+    self.assertEqual(bloom_check('account', 101234), 1)
+    self.assertEqual(bloom_check('account', 101234.0), 1)
+    self.assertEqual(bloom_check('account', 99999), 0)
+    self.assertEqual(bloom_check('score', 308.5), 1)
+    self.assertEqual(bloom_check('score', 308.6), 0)
+    self.assertEqual(bloom_check('account', None), 0)
+    # values that don't read as exactly one parquet value might be there
+    self.assertEqual(bloom_check('account', '101234'), 1)
+    self.assertEqual(bloom_check('score', 0), 1)
+    # no bloom filter to check
+    self.assertEqual(bloom_check('id', 5), None)
+    self.assertEqual(db.execute("select parquet_bloom_check('tests/data/numbers.parquet', 'ints', 1)").fetchone()[0], None)
+    with open('tests/data/bloom.parquet', 'rb') as f:
+      self.assertEqual(db.execute("select parquet_bloom_check(?, 'user_id', 'user-01234x')", [f.read()]).fetchone()[0], 0)
+    with self.assertRaisesRegex(sqlite3.OperationalError, "parquet file 'tests/data/bloom.parquet': column 'nope' doesn't exist"):
+      bloom_check('nope', 1)
+
   
     
   def test_parquet(self):
@@ -64,7 +100,7 @@ class TestParquet(unittest.TestCase):
       execute_all("select * from numbers"),
        [
         {'bools': 1,
-         'dates': '2018-01-01 00:00:00.000',
+         'dates': '2018-01-01 00:00:00.000000',
          'int16': 1,
          'int32': 1,
          'int64': 1,
@@ -76,7 +112,7 @@ class TestParquet(unittest.TestCase):
          'uint8': 1,
          'umm': 3.14},
         {'bools': 0,
-         'dates': '2019-12-31 00:00:00.000',
+         'dates': '2019-12-31 00:00:00.000000',
          'int16': 2,
          'int32': 2,
          'int64': 2,
@@ -88,7 +124,15 @@ class TestParquet(unittest.TestCase):
          'uint8': 2,
          'umm': 6.28}]
     )
-  
+    self.assertEqual(
+      execute_all("select uint8, dates, ints from numbers"),
+      [
+        {'uint8': 1, 'dates': '2018-01-01 00:00:00.000000', 'ints': 1},
+        {'uint8': 2, 'dates': '2019-12-31 00:00:00.000000', 'ints': 2}
+      ]
+    )
+    self.assertEqual(execute_all("select count(*) as n from numbers"), [{'n': 2}])
+
     db.execute("create virtual table json using parquet(filename='tests/data/json.parquet');").fetchone()
     
     self.assertEqual(
@@ -112,9 +156,9 @@ class TestParquet(unittest.TestCase):
     self.assertEqual(
       execute_all("select * from dates;"),
        [
-        {'dates': '2018-01-01 00:00:00.000'},
-        {'dates': '2019-12-31 00:00:00.000'},
-        {'dates': '2020-10-31 19:00:00.000'}
+        {'dates': '2018-01-01 00:00:00.000000'},
+        {'dates': '2019-12-31 00:00:00.000000'},
+        {'dates': '2020-10-31 19:00:00.000000'}
       ]
     )
 
@@ -122,10 +166,955 @@ class TestParquet(unittest.TestCase):
     self.assertEqual(
       execute_all("select * from duck;"),
         [
-          { 'col0': 1, 'col1': 'alex', 'col2': b'\x00\x00\x00\x01', 'col3': '2022-10-26 23:01:24.303', 'col4': '1999-12-31'},
-          { 'col0': 2, 'col1': 'brian', 'col2': b'\x00\x00\x00\x02', 'col3': '2022-10-26 23:01:24.303', 'col4': '2000-01-01'}
+          { 'col0': 1, 'col1': 'alex', 'col2': b'\x00\x00\x00\x01', 'col3': '2022-10-26 23:01:24.303000', 'col4': '1999-12-31'},
+          { 'col0': 2, 'col1': 'brian', 'col2': b'\x00\x00\x00\x02', 'col3': '2022-10-26 23:01:24.303000', 'col4': '2000-01-01'}
         ]
     )
+
+    db.execute("create virtual table row_groups using parquet(filename='tests/data/row_groups.parquet');").fetchone()
+    self.assertEqual(
+      execute_all("select count(*) as n, min(id) as lo, max(id) as hi from row_groups where id between 30 and 39"),
+      [{'n': 10, 'lo': 30, 'hi': 39}]
+    )
+    self.assertEqual(execute_all("select name from row_groups where id = 55"), [{'name': 'name055'}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where name >= 'name090'"), [{'n': 10}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where value > 45"), [{'n': 7}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where value is null"), [{'n': 23}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where id > 45.5"), [{'n': 54}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where id = null"), [{'n': 0}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where id < '5'"), [{'n': 5}])
+    # statistics are sorted byte by byte, so other collations aren't pruned with them
+    self.assertEqual(execute_all("select count(*) as n from row_groups where name = 'NAME055' collate nocase"), [{'n': 1}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where name < 'NAME010' collate nocase"), [{'n': 10}])
+
+    self.assertEqual(execute_all("select count(distinct rowid) as n, min(rowid) as lo, max(rowid) as hi from row_groups"), [{'n': 100, 'lo': 0, 'hi': 99}])
+    self.assertEqual(execute_all("select rowid, id from row_groups where rowid = 55"), [{'rowid': 55, 'id': 55}])
+    self.assertEqual(
+      execute_all("select rowid, id from row_groups where rowid between 38 and 42"),
+      [{'rowid': i, 'id': i} for i in range(38, 43)]
+    )
+    self.assertEqual(execute_all("select count(*) as n from row_groups where rowid > 89.5"), [{'n': 10}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where rowid < 3"), [{'n': 3}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where rowid = 100"), [{'n': 0}])
+    self.assertEqual(execute_all("select count(*) as n from row_groups where rowid = null"), [{'n': 0}])
+    self.assertEqual(execute_all("select id from row_groups where rowid > 60 and value > 45"), [{'id': 92}, {'id': 93}, {'id': 94}, {'id': 95}, {'id': 96}, {'id': 97}, {'id': 99}])
+    self.assertEqual(execute_all("select rowid from numbers"), [{'rowid': 0}, {'rowid': 1}])
+
+  def test_parquet_declared_types(self):
+    def columns(table):
+      return [(row[1], row[2], row[3]) for row in db.execute(f"pragma table_info({table})").fetchall()]
+    db.execute("create virtual table declared_duck using parquet(filename='tests/data/duck.parquet')")
+    self.assertEqual(
+      columns("declared_duck"),
+      [('col0', 'INTEGER', 1), ('col1', 'TEXT', 1), ('col2', 'BLOB', 1), ('col3', 'TIMESTAMP', 1), ('col4', 'DATE', 1)]
+    )
+    db.execute("create virtual table declared_json using parquet(filename='tests/data/json.parquet')")
+    self.assertEqual(
+      columns("declared_json"),
+      [('json_array', 'JSON', 1), ('list_bytes', 'JSON', 1), ('ummm', 'JSON', 1)]
+    )
+    db.execute("create virtual table declared_row_groups using parquet(filename='tests/data/row_groups.parquet')")
+    self.assertEqual(
+      columns("declared_row_groups"),
+      [('id', 'INTEGER', 1), ('name', 'TEXT', 1), ('value', 'REAL', 0)]
+    )
+    db.execute("create virtual table declared_union using parquet(filename='tests/data/union/*.parquet', union_by_name=1)")
+    self.assertEqual(
+      columns("declared_union"),
+      [('id', 'INTEGER', 1), ('name', 'TEXT', 0), ('score', 'REAL', 1), ('value', 'REAL', 0)]
+    )
+
+  def test_parquet_decimals(self):
+    db.execute("create virtual table decimals using parquet(filename='tests/data/decimals.parquet')")
+    self.assertEqual(
+      execute_all("select * from decimals"),
+      [
+        {'d32': '123.45', 'd64': '123456789.0123', 'dfixed': '1234567890123456789012345678.9012345678'},
+        {'d32': '-0.05', 'd64': '-0.0001', 'dfixed': '-0.0000000001'},
+        {'d32': '0.00', 'd64': '5.0000', 'dfixed': None},
+      ]
+    )
+    self.assertEqual(
+      [(row[1], row[2]) for row in db.execute("pragma table_info(decimals)").fetchall()],
+      [('d32', 'DECIMAL(9,2)'), ('d64', 'DECIMAL(18,4)'), ('dfixed', 'DECIMAL(38,10)')]
+    )
+    # DECIMAL has numeric affinity, so text values still compare as numbers
+    self.assertEqual(execute_all("select d32 from decimals where d32 > 1"), [{'d32': '123.45'}])
+
+    db.execute("create virtual table decimals_real using parquet(filename='tests/data/decimals.parquet', decimal=real)")
+    self.assertEqual(
+      execute_all("select d32, d64, dfixed from decimals_real"),
+      [
+        {'d32': 123.45, 'd64': 123456789.0123, 'dfixed': 1.2345678901234568e+27},
+        {'d32': -0.05, 'd64': -0.0001, 'dfixed': -1e-10},
+        {'d32': 0.0, 'd64': 5.0, 'dfixed': None},
+      ]
+    )
+
+    db.execute("create virtual table decimals_int using parquet(filename='tests/data/decimals.parquet', decimal=int_scaled)")
+    self.assertEqual(
+      execute_all("select d32, d64 from decimals_int"),
+      [{'d32': 12345, 'd64': 1234567890123}, {'d32': -5, 'd64': -1}, {'d32': 0, 'd64': 50000}]
+    )
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'dfixed': decimal too large for an integer: 12345678901234567890123456789012345678"):
+      execute_all("select dfixed from decimals_int")
+
+    with self.assertRaisesRegex(sqlite3.OperationalError, "invalid decimal value 'float', expected text, real or int_scaled"):
+      db.execute("create virtual table decimals_invalid using parquet(filename='tests/data/decimals.parquet', decimal=float)")
+
+  def test_parquet_timestamps(self):
+    db.execute("create virtual table timestamps using parquet(filename='tests/data/timestamps.parquet')")
+    self.assertEqual(
+      execute_all("select * from timestamps"),
+      [
+        {'ts_millis': '2022-10-25 12:34:56.789Z', 'ts_micros': '2022-10-25 12:34:56.789123', 'ts_nanos': '2022-10-25 12:34:56.789123456Z'},
+        {'ts_millis': '1969-12-31 23:59:59.500Z', 'ts_micros': '1969-12-31 23:59:59.500000', 'ts_nanos': '1969-12-31 23:59:59.500000000Z'},
+        {'ts_millis': None, 'ts_micros': None, 'ts_nanos': None},
+      ]
+    )
+    self.assertEqual(
+      [row[2] for row in db.execute("pragma table_info(timestamps)").fetchall()],
+      ['TIMESTAMP', 'TIMESTAMP', 'TIMESTAMP']
+    )
+    self.assertEqual(
+      execute_all("select datetime(ts_millis) as a, datetime(ts_nanos) as b from timestamps where ts_micros < '2000-01-01'"),
+      [{'a': '1969-12-31 23:59:59', 'b': '1969-12-31 23:59:59'}]
+    )
+
+    forms = {
+      'unixepoch': ('INTEGER', [1666701296, -1]),
+      'unixepoch_ms': ('INTEGER', [1666701296789, -500]),
+      'julianday': ('REAL', [2459878.0242683925, 2440587.499994213]),
+    }
+    for form, (declared, values) in forms.items():
+      db.execute(f"create virtual table timestamps_{form} using parquet(filename='tests/data/timestamps.parquet', timestamps={form})")
+      self.assertEqual(
+        [row[2] for row in db.execute(f"pragma table_info(timestamps_{form})").fetchall()],
+        [declared] * 3
+      )
+      for column in ['ts_millis', 'ts_micros', 'ts_nanos']:
+        rows = db.execute(f"select {column} from timestamps_{form} where {column} is not null").fetchall()
+        self.assertEqual(len(rows), 2)
+        for (value,), expected in zip(rows, values):
+          self.assertAlmostEqual(value, expected, places=6)
+    self.assertEqual(
+      execute_all("select count(*) as n from timestamps_unixepoch_ms where ts_nanos >= 0"),
+      [{'n': 1}]
+    )
+
+    with self.assertRaisesRegex(sqlite3.OperationalError, "invalid timestamps value 'iso', expected iso8601, unixepoch, unixepoch_ms or julianday"):
+      db.execute("create virtual table timestamps_invalid using parquet(filename='tests/data/timestamps.parquet', timestamps=iso)")
+
+  def test_parquet_int96(self):
+    db.execute("create virtual table int96 using parquet(filename='tests/data/int96.parquet')")
+    self.assertEqual(
+      execute_all("select * from int96"),
+      [
+        {'id': 1, 'ts': '2022-10-25 12:34:56.789123456'},
+        {'id': 2, 'ts': '1969-12-31 23:59:59.500000000'},
+        {'id': 3, 'ts': None},
+      ]
+    )
+    self.assertEqual(execute_all("select ts from int96 where rowid = 1"), [{'ts': '1969-12-31 23:59:59.500000000'}])
+    self.assertEqual(
+      [row[2] for row in db.execute("pragma table_info(int96)").fetchall()],
+      ['INTEGER', 'TIMESTAMP']
+    )
+    db.execute("create virtual table int96_ms using parquet(filename='tests/data/int96.parquet', timestamps=unixepoch_ms)")
+    self.assertEqual(execute_all("select ts from int96_ms"), [{'ts': 1666701296789}, {'ts': -500}, {'ts': None}])
+    self.assertEqual(
+      execute_all("select stats_min, stats_max from parquet_column_chunks('tests/data/int96.parquet') where column_type = 'INT96'"),
+      [{'stats_min': '1969-12-31 23:59:59.500000000', 'stats_max': '2022-10-25 12:34:56.789123456'}]
+    )
+
+  def test_parquet_logical_types(self):
+    db.execute("create virtual table logical using parquet(filename='tests/data/logical.parquet')")
+    self.assertEqual(
+      execute_all("select * from logical"),
+      [
+        {
+          'time_ms': '12:34:56.789',
+          'time_us': '12:34:56.789123',
+          'time_ns': '12:34:56.789123456',
+          'duration': 'P1Y2M3DT4H5M6.789S',
+          'id': 'f81d4fae-7dec-11d0-a765-00a0c91e6bf6',
+          'color': 'red',
+          'doc': '{"a":[1,2]}',
+          'bson': '{"n":1,"name":"alex","score":2.5,"tags":["x"],"when":{"$date":"2022-10-25T12:34:56.789Z"}}',
+        },
+        {
+          'time_ms': '00:00:00.000',
+          'time_us': '23:59:59.999999',
+          'time_ns': '00:00:00.000000001',
+          'duration': 'PT0S',
+          'id': '00000000-0000-0000-0000-000000000000',
+          'color': 'green',
+          'doc': '[]',
+          'bson': '{}',
+        },
+        {
+          'time_ms': None, 'time_us': None, 'time_ns': None, 'duration': None,
+          'id': None, 'color': None, 'doc': None, 'bson': None,
+        },
+      ]
+    )
+    self.assertEqual(
+      execute_all("select doc -> '$.a[1]' as a, bson ->> '$.tags[0]' as tag from logical where rowid = 0"),
+      [{'a': '2', 'tag': 'x'}]
+    )
+    self.assertEqual(execute_all("select count(*) as n from logical where time_us > '12:00'"), [{'n': 2}])
+    self.assertEqual(
+      [row[2] for row in db.execute("pragma table_info(logical)").fetchall()],
+      ['TIME', 'TIME', 'TIME', 'TEXT', 'TEXT', 'TEXT', 'JSON', 'JSON']
+    )
+
+  def test_parquet_float16(self):
+    db.execute("create virtual table float16 using parquet(filename='tests/data/float16.parquet')")
+    self.assertEqual(
+      execute_all("select * from float16"),
+      [
+        {'id': 1, 'half': b'\x00\x3e'},
+        {'id': 2, 'half': None},
+        {'id': 3, 'half': b'\x00\xb4'},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select path, physical_type, logical_type, type_length from parquet_columns('tests/data/float16.parquet') where path = 'half'"),
+      [{'path': 'half', 'physical_type': 'FIXED_LEN_BYTE_ARRAY', 'logical_type': None, 'type_length': 2}]
+    )
+    with open('tests/data/float16.parquet', 'rb') as f:
+      blob = f.read()
+    self.assertEqual(execute_all("select num_rows, num_columns from parquet_metadata(?)", [blob]), [{'num_rows': 3, 'num_columns': 2}])
+
+  def test_parquet_uint64_overflow(self):
+    db.execute("create virtual table uint64 using parquet(filename='tests/data/uint64.parquet')")
+    self.assertEqual(
+      execute_all("select big from uint64"),
+      [{'big': 1}, {'big': '9223372036854775808'}, {'big': '18446744073709551615'}, {'big': None}]
+    )
+    self.assertEqual(execute_all("select id from uint64 where big > 5"), [{'id': 2}, {'id': 3}])
+    db.execute("create virtual table uint64_real using parquet(filename='tests/data/uint64.parquet', uint64_overflow=real)")
+    self.assertEqual(
+      execute_all("select big from uint64_real"),
+      [{'big': 1}, {'big': 9223372036854775808.0}, {'big': 18446744073709551615.0}, {'big': None}]
+    )
+    db.execute("create virtual table uint64_null using parquet(filename='tests/data/uint64.parquet', uint64_overflow=null)")
+    self.assertEqual(execute_all("select id from uint64_null where big is null"), [{'id': 2}, {'id': 3}, {'id': 4}])
+    db.execute("create virtual table uint64_error using parquet(filename='tests/data/uint64.parquet', uint64_overflow=error)")
+    self.assertEqual(execute_all("select big from uint64_error where id = 1"), [{'big': 1}])
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'big': value too large for an integer: 9223372036854775808"):
+      db.execute("select big from uint64_error").fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "invalid uint64_overflow value 'bogus', expected error, real, text or null"):
+      db.execute("create virtual table uint64_bogus using parquet(filename='tests/data/uint64.parquet', uint64_overflow=bogus)")
+
+    chunks = "select stats_min, stats_max from parquet_column_chunks('tests/data/uint64.parquet'{}) where column_type = 'INT64' and row_group = 0 and stats_null_count = 1"
+    self.assertEqual(execute_all(chunks.format('')), [{'stats_min': 1, 'stats_max': '18446744073709551615'}])
+    self.assertEqual(execute_all(chunks.format(", 'real'")), [{'stats_min': 1, 'stats_max': 18446744073709551615.0}])
+    with self.assertRaisesRegex(sqlite3.OperationalError, "value too large for an integer"):
+      db.execute(chunks.format(", 'error'")).fetchall()
+
+  def test_parquet_nested_flatten(self):
+    db.execute("create virtual table nested_json using parquet(filename='tests/data/nested.parquet')")
+    self.assertEqual(
+      execute_all("select address from nested_json where id = 1"),
+      [{'address': '{"city":"Paris","geo":{"lat":48.86,"lon":2.35},"zip":75001}'}]
+    )
+    db.execute("create virtual table nested using parquet(filename='tests/data/nested.parquet', nested=flatten)")
+    self.assertEqual(
+      [(row[1], row[2], row[3]) for row in db.execute("pragma table_info(nested)").fetchall()],
+      [
+        ('id', 'INTEGER', 1),
+        ('address.city', 'TEXT', 0),
+        ('address.zip', 'INTEGER', 0),
+        ('address.geo.lat', 'REAL', 0),
+        ('address.geo.lon', 'REAL', 0),
+        ('tags', 'JSON', 0),
+      ]
+    )
+    self.assertEqual(
+      execute_all("select * from nested"),
+      [
+        {'id': 1, 'address.city': 'Paris', 'address.zip': 75001, 'address.geo.lat': 48.86, 'address.geo.lon': 2.35, 'tags': '["a"]'},
+        {'id': 2, 'address.city': 'Berlin', 'address.zip': 10115, 'address.geo.lat': None, 'address.geo.lon': None, 'tags': '[]'},
+        {'id': 3, 'address.city': None, 'address.zip': None, 'address.geo.lat': None, 'address.geo.lon': None, 'tags': None},
+        {'id': 4, 'address.city': 'Tokyo', 'address.zip': None, 'address.geo.lat': 35.68, 'address.geo.lon': 139.69, 'tags': '["b","c"]'},
+      ]
+    )
+    self.assertEqual(execute_all('select "address.geo.lon" as lon from nested where id = 4'), [{'lon': 139.69}])
+
+    # constraints on struct leaves prune row groups by their statistics
+    db.execute("select parquet_trace('info')")
+    last = db.execute("select coalesce(max(rowid), 0) from parquet_trace_log").fetchone()[0]
+    self.assertEqual(
+      execute_all('select id, "address.city" as city from nested where "address.zip" = 10115'),
+      [{'id': 2, 'city': 'Berlin'}]
+    )
+    self.assertEqual(
+      execute_all("select message from parquet_trace_log where event = 'prune row groups' and rowid > ?", [last]),
+      [{'message': 'reading 1 of 2 row groups, 3 of 6 columns'}]
+    )
+    db.execute("select parquet_trace('off')")
+    self.assertEqual(execute_all('select id from nested where "address.city" is null'), [{'id': 3}])
+
+    with self.assertRaisesRegex(sqlite3.OperationalError, "invalid nested value 'deep', expected json or flatten"):
+      db.execute("create virtual table nested_bogus using parquet(filename='tests/data/nested.parquet', nested=deep)")
+
+  def test_parquet_each(self):
+    self.assertEqual(
+      execute_all("select * from parquet_each('tests/data/lists.parquet', 'tags')"),
+      [
+        {'row_index': 0, 'position': 0, 'key': None, 'value': 'a'},
+        {'row_index': 0, 'position': 1, 'key': None, 'value': None},
+        {'row_index': 0, 'position': 2, 'key': None, 'value': 'b'},
+        {'row_index': 3, 'position': 0, 'key': None, 'value': 'c'},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select row_index, position, key, value from parquet_each('tests/data/lists.parquet', 'attrs')"),
+      [
+        {'row_index': 0, 'position': 0, 'key': 'x', 'value': 1},
+        {'row_index': 0, 'position': 1, 'key': 'y', 'value': 2},
+        {'row_index': 3, 'position': 0, 'key': 'z', 'value': None},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n from parquet_each where source = 'tests/data/lists.parquet' and column_path = 'tags' and value is not null"),
+      [{'n': 3}]
+    )
+    # the file can be a blob, like from readfile()
+    with open('tests/data/lists.parquet', 'rb') as f:
+      blob = f.read()
+    self.assertEqual(
+      execute_all("select row_index, value, source = ? as same_source from parquet_each(?, 'tags')", [blob, blob]),
+      [
+        {'row_index': 0, 'value': 'a', 'same_source': 1},
+        {'row_index': 0, 'value': None, 'same_source': 1},
+        {'row_index': 0, 'value': 'b', 'same_source': 1},
+        {'row_index': 3, 'value': 'c', 'same_source': 1},
+      ]
+    )
+    with self.assertRaisesRegex(sqlite3.OperationalError, "parquet file '<blob>': column 'nope' doesn't exist"):
+      db.execute("select * from parquet_each(?, 'nope')", [blob]).fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'matrix' is inside another list, which isn't supported"):
+      db.execute("select * from parquet_each('tests/data/lists.parquet', 'matrix')").fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'id' isn't a list or a map"):
+      db.execute("select * from parquet_each('tests/data/lists.parquet', 'id')").fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'nope' doesn't exist"):
+      db.execute("select * from parquet_each('tests/data/lists.parquet', 'nope')").fetchall()
+
+  def test_parquet_column_values(self):
+    self.assertEqual(
+      execute_all("select * from parquet_column_values('tests/data/lists.parquet', 'tags.list.item')"),
+      [
+        {'row_group': 0, 'row_index': 0, 'value': 'a', 'definition_level': 3, 'repetition_level': 0},
+        {'row_group': 0, 'row_index': 0, 'value': None, 'definition_level': 2, 'repetition_level': 1},
+        {'row_group': 0, 'row_index': 0, 'value': 'b', 'definition_level': 3, 'repetition_level': 1},
+        {'row_group': 0, 'row_index': 1, 'value': None, 'definition_level': 1, 'repetition_level': 0},
+        {'row_group': 1, 'row_index': 2, 'value': None, 'definition_level': 0, 'repetition_level': 0},
+        {'row_group': 1, 'row_index': 3, 'value': 'c', 'definition_level': 3, 'repetition_level': 0},
+      ]
+    )
+    with open('tests/data/nested.parquet', 'rb') as f:
+      blob = f.read()
+    self.assertEqual(
+      execute_all("select row_group, row_index, value, definition_level from parquet_column_values(?, 'address.zip') limit 3", [blob]),
+      [
+        {'row_group': 0, 'row_index': 0, 'value': 75001, 'definition_level': 2},
+        {'row_group': 0, 'row_index': 1, 'value': 10115, 'definition_level': 2},
+        {'row_group': 1, 'row_index': 2, 'value': None, 'definition_level': 0},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n, max(row_index) as last from parquet_column_values where source = 'tests/data/nested.parquet' and column = 'id'"),
+      [{'n': 4, 'last': 3}]
+    )
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'address' isn't a leaf column"):
+      db.execute("select * from parquet_column_values('tests/data/nested.parquet', 'address')").fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "column 'nope' doesn't exist"):
+      db.execute("select * from parquet_column_values('tests/data/nested.parquet', 'nope')").fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "parquet file '<blob>'"):
+      db.execute("select * from parquet_column_values(x'00', 'id')").fetchall()
+
+  def test_parquet_multiple_files(self):
+    db.execute("create virtual table parts using parquet(filename='tests/data/parts/part-*.parquet')")
+    self.assertEqual(execute_all("select count(*) as n, min(id) as lo, max(id) as hi from parts"), [{'n': 30, 'lo': 0, 'hi': 29}])
+    self.assertEqual(execute_all("select * from parts where rowid = 12"), [{'id': 12, 'name': 'name012'}])
+    self.assertEqual(
+      execute_all("select _filename, count(*) as n, min(rowid) as lo, max(rowid) as hi from parts group by 1"),
+      [
+        {'_filename': 'tests/data/parts/part-0.parquet', 'n': 10, 'lo': 0, 'hi': 9},
+        {'_filename': 'tests/data/parts/part-1.parquet', 'n': 10, 'lo': 10, 'hi': 19},
+        {'_filename': 'tests/data/parts/part-2.parquet', 'n': 10, 'lo': 20, 'hi': 29},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select min(id) as lo, max(id) as hi from parts where _filename = 'tests/data/parts/part-1.parquet'"),
+      [{'lo': 10, 'hi': 19}]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n from parts where _filename in ('tests/data/parts/part-0.parquet', 'tests/data/parts/part-2.parquet')"),
+      [{'n': 20}]
+    )
+    self.assertEqual(execute_all("select count(*) as n from parts where _filename > 'tests/data/parts/part-0.parquet' and id < 15"), [{'n': 5}])
+    self.assertEqual(execute_all("select count(*) as n from parts where _filename = 'nope.parquet'"), [{'n': 0}])
+    # file names are only pruned with the BINARY collation
+    self.assertEqual(
+      execute_all("select min(id) as lo, max(id) as hi from parts where _filename = 'TESTS/data/parts/part-1.parquet' collate nocase"),
+      [{'lo': 10, 'hi': 19}]
+    )
+    self.assertEqual(execute_all("select count(*) as n from parts where _filename is null"), [{'n': 0}])
+
+    db.execute("create virtual table listed_parts using parquet(filename='tests/data/parts/part-2.parquet', filename='tests/data/parts/part-0.parquet')")
+    self.assertEqual(
+      execute_all("select _filename, min(id) as lo from listed_parts group by 1 order by min(rowid)"),
+      [
+        {'_filename': 'tests/data/parts/part-2.parquet', 'lo': 20},
+        {'_filename': 'tests/data/parts/part-0.parquet', 'lo': 0},
+      ]
+    )
+
+    with self.assertRaisesRegex(sqlite3.OperationalError, "no parquet files match 'tests/data/parts/nope-\\*.parquet'"):
+      db.execute("create virtual table no_parts using parquet(filename='tests/data/parts/nope-*.parquet')")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "parquet file 'tests/data/mismatch/b.parquet' has a different schema than 'tests/data/mismatch/a.parquet'"):
+      db.execute("create virtual table mismatch using parquet(filename='tests/data/mismatch/*.parquet')")
+
+  def test_parquet_union_by_name(self):
+    db.execute("create virtual table union_files using parquet(filename='tests/data/union/*.parquet', union_by_name=1)")
+    self.assertEqual(
+      execute_all("select rowid, * from union_files"),
+      [
+        {'rowid': 0, 'id': 1, 'name': 'alex', 'score': 1.5, 'value': None},
+        {'rowid': 1, 'id': 2, 'name': 'brian', 'score': 2.5, 'value': None},
+        {'rowid': 2, 'id': 3, 'name': None, 'score': 3.25, 'value': 10.0},
+        {'rowid': 3, 'id': 4, 'name': None, 'score': 4.25, 'value': None},
+      ]
+    )
+    self.assertEqual(execute_all("select id from union_files where name is null"), [{'id': 3}, {'id': 4}])
+    self.assertEqual(execute_all("select id from union_files where id >= 2 and id < 4"), [{'id': 2}, {'id': 3}])
+    self.assertEqual(execute_all("select id from union_files where value > 5"), [{'id': 3}])
+
+    with self.assertRaisesRegex(sqlite3.OperationalError, "parquet file 'tests/data/mismatch/b.parquet' has column 'id' as BYTE_ARRAY \\(UTF8\\), which doesn't fit INT64 from 'tests/data/mismatch/a.parquet'"):
+      db.execute("create virtual table union_mismatch using parquet(filename='tests/data/mismatch/*.parquet', union_by_name=1)")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "invalid union_by_name value 'yes'"):
+      db.execute("create virtual table union_invalid using parquet(filename='tests/data/union/*.parquet', union_by_name=yes)")
+
+  def test_parquet_partitions(self):
+    db.execute("create virtual table hive using parquet(filename='tests/data/hive')")
+    self.assertEqual(
+      [(row[1], row[2]) for row in db.execute("pragma table_info(hive)").fetchall()],
+      [('id', 'INTEGER'), ('date', 'TEXT'), ('region', 'TEXT')]
+    )
+    self.assertEqual(
+      execute_all("select date, region, count(*) as n, min(id) as lo from hive group by 1, 2 order by lo"),
+      [
+        {'date': '2022-10-25', 'region': 'us', 'n': 5, 'lo': 0},
+        {'date': '2022-10-25', 'region': 'eu', 'n': 5, 'lo': 10},
+        {'date': '2022-10-26', 'region': 'us', 'n': 5, 'lo': 20},
+        {'date': '2022-10-26', 'region': 'a/b', 'n': 5, 'lo': 30},
+        {'date': None, 'region': 'us', 'n': 1, 'lo': 90},
+      ]
+    )
+    self.assertEqual(execute_all("select count(*) as n from hive where region = 'us'"), [{'n': 11}])
+    self.assertEqual(execute_all("select count(*) as n from hive where date >= '2022-10-26' and region = 'us'"), [{'n': 5}])
+    self.assertEqual(execute_all("select count(*) as n from hive where date is null"), [{'n': 1}])
+    self.assertEqual(execute_all("select count(*) as n from hive where date = '2022-10-27'"), [{'n': 0}])
+    # partition values are only pruned with the BINARY collation
+    self.assertEqual(execute_all("select count(*) as n from hive where region = 'US' collate nocase"), [{'n': 11}])
+
+    # only the files that match are opened
+    db.execute("select parquet_trace('info')")
+    last = db.execute("select coalesce(max(rowid), 0) from parquet_trace_log").fetchone()[0]
+    execute_all("select id from hive where date = '2022-10-25' and region = 'eu'")
+    self.assertEqual(
+      execute_all("select path from parquet_trace_log where event = 'open file' and rowid > ?", [last]),
+      [{'path': 'tests/data/hive/date=2022-10-25/region=eu/part-1.parquet'}]
+    )
+    db.execute("select parquet_trace('off')")
+
+    db.execute("create virtual table years using parquet(filename='tests/data/years/*/*.parquet')")
+    self.assertEqual(
+      [(row[1], row[2]) for row in db.execute("pragma table_info(years)").fetchall()],
+      [('id', 'INTEGER'), ('year', 'INTEGER')]
+    )
+    self.assertEqual(execute_all("select id, year from years where year > 2021"), [{'id': 2022, 'year': 2022}])
+    self.assertEqual(execute_all("select id, year from years where year = '2021'"), [{'id': 2021, 'year': 2021}])
+
+    # only directories below the filename= root are partitions
+    for filename in ['tests/data/hive/date=2022-10-25', 'tests/data/hive/date=2022-10-25/*/*.parquet']:
+      db.execute(f"create virtual table hive_root using parquet(filename='{filename}')")
+      self.assertEqual(
+        [(row[1], row[2]) for row in db.execute("pragma table_info(hive_root)").fetchall()],
+        [('id', 'INTEGER'), ('region', 'TEXT')]
+      )
+      db.execute("drop table hive_root")
+    db.execute("create virtual table hive_file using parquet(filename='tests/data/hive/date=2022-10-25/region=eu/part-1.parquet')")
+    self.assertEqual(
+      [(row[1], row[2]) for row in db.execute("pragma table_info(hive_file)").fetchall()],
+      [('id', 'INTEGER')]
+    )
+
+  def test_parquet_metadata(self):
+    self.assertEqual(
+      execute_all("select version, num_rows, num_columns, num_row_groups from parquet_metadata('tests/data/row_groups.parquet')"),
+      [{'version': 1, 'num_rows': 100, 'num_columns': 3, 'num_row_groups': 10}]
+    )
+    self.assertEqual(
+      execute_all("select num_rows from parquet_metadata('tests/data/row_groups.parquet') where num_columns = 4"),
+      []
+    )
+
+  def test_parquet_kv_metadata(self):
+    arrow_schema = {
+      'fields': [
+        {'name': 'id', 'type': 'Int64', 'nullable': False, 'metadata': {'comment': 'primary key'}},
+        {'name': 'at', 'type': 'Timestamp(Millisecond, UTC)', 'nullable': True},
+        {'name': 'tags', 'type': 'List', 'nullable': True, 'children': [{'name': 'item', 'type': 'Utf8', 'nullable': True}]},
+      ],
+      'metadata': {'origin': 'fixtures'},
+    }
+    rows = execute_all("select key, value from parquet_kv_metadata('tests/data/kv.parquet')")
+    self.assertEqual(
+      [row for row in rows if row['key'] != 'ARROW:schema'],
+      [
+        {'key': 'pipeline', 'value': 'nightly'},
+        {'key': 'pandas', 'value': '{"index_columns": [], "columns": [{"name": "id"}]}'},
+        {'key': 'empty', 'value': None},
+      ]
+    )
+    self.assertEqual(
+      json.loads(db.execute("select value from parquet_kv_metadata('tests/data/kv.parquet') where key = 'ARROW:schema'").fetchone()[0]),
+      arrow_schema
+    )
+    self.assertEqual(
+      db.execute("select json_extract(value, '$.fields[2].children[0].type') from parquet_kv_metadata('tests/data/kv.parquet') where key = 'ARROW:schema'").fetchone()[0],
+      'Utf8'
+    )
+    metadata = json.loads(db.execute("select key_value_metadata from parquet_metadata('tests/data/kv.parquet')").fetchone()[0])
+    self.assertEqual(metadata, {
+      'ARROW:schema': arrow_schema,
+      'empty': None,
+      'pandas': '{"index_columns": [], "columns": [{"name": "id"}]}',
+      'pipeline': 'nightly',
+    })
+    self.assertEqual(
+      db.execute("select json_extract(key_value_metadata, '$.pipeline') from parquet_metadata('tests/data/kv.parquet')").fetchone()[0],
+      'nightly'
+    )
+
+  def test_parquet_row_groups(self):
+    self.assertEqual(
+      execute_all("select count(*) as n, sum(num_rows) as rows, min(row_group) as lo, max(row_group) as hi from parquet_row_groups('tests/data/row_groups.parquet')"),
+      [{'n': 10, 'rows': 100, 'lo': 0, 'hi': 9}]
+    )
+    self.assertEqual(
+      execute_all("""
+        select
+          (select sum(compressed_size) from parquet_column_chunks('tests/data/row_groups.parquet') where row_group = 3) = compressed_size as sizes_match,
+          total_byte_size > 0 as has_size,
+          file_offset > 0 as has_offset,
+          ordinal,
+          sorting_columns
+        from parquet_row_groups('tests/data/row_groups.parquet') where row_group = 3
+      """),
+      [{'sizes_match': 1, 'has_size': 1, 'has_offset': 1, 'ordinal': None, 'sorting_columns': None}]
+    )
+    # row groups follow each other in the file
+    self.assertEqual(
+      execute_all("""
+        select count(*) as n from parquet_row_groups('tests/data/row_groups.parquet') a
+        join parquet_row_groups('tests/data/row_groups.parquet') b on b.row_group = a.row_group + 1
+        where b.file_offset >= a.file_offset + a.compressed_size
+      """),
+      [{'n': 9}]
+    )
+    with open('tests/data/row_groups.parquet', 'rb') as f:
+      blob = f.read()
+    self.assertEqual(
+      execute_all("select count(*) as n from parquet_row_groups(?)", [blob]),
+      [{'n': 10}]
+    )
+    self.assertEqual(
+      execute_all("select num_row_groups from parquet_metadata(?)", [blob]),
+      [{'num_row_groups': 10}]
+    )
+    with self.assertRaisesRegex(sqlite3.OperationalError, "could not open parquet file 'nope.parquet'"):
+      db.execute("select * from parquet_row_groups('nope.parquet')").fetchall()
+
+  def test_parquet_columns(self):
+    self.assertEqual(
+      execute_all("select * from parquet_columns('tests/data/lists.parquet')"),
+      [
+        {'name': 'id', 'path': 'id', 'physical_type': 'INT64', 'logical_type': None, 'converted_type': None, 'repetition': 'REQUIRED', 'max_definition_level': 0, 'max_repetition_level': 0, 'type_length': None, 'precision': None, 'scale': None, 'field_id': None},
+        {'name': 'item', 'path': 'tags.list.item', 'physical_type': 'BYTE_ARRAY', 'logical_type': 'STRING', 'converted_type': 'UTF8', 'repetition': 'OPTIONAL', 'max_definition_level': 3, 'max_repetition_level': 1, 'type_length': None, 'precision': None, 'scale': None, 'field_id': None},
+        {'name': 'keys', 'path': 'attrs.entries.keys', 'physical_type': 'BYTE_ARRAY', 'logical_type': 'STRING', 'converted_type': 'UTF8', 'repetition': 'REQUIRED', 'max_definition_level': 2, 'max_repetition_level': 1, 'type_length': None, 'precision': None, 'scale': None, 'field_id': None},
+        {'name': 'values', 'path': 'attrs.entries.values', 'physical_type': 'INT64', 'logical_type': None, 'converted_type': None, 'repetition': 'OPTIONAL', 'max_definition_level': 3, 'max_repetition_level': 1, 'type_length': None, 'precision': None, 'scale': None, 'field_id': None},
+        {'name': 'item', 'path': 'matrix.list.item.list.item', 'physical_type': 'INT32', 'logical_type': None, 'converted_type': None, 'repetition': 'OPTIONAL', 'max_definition_level': 4, 'max_repetition_level': 2, 'type_length': None, 'precision': None, 'scale': None, 'field_id': None},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select path, logical_type, converted_type, type_length, precision, scale from parquet_columns('tests/data/decimals.parquet')"),
+      [
+        {'path': 'd32', 'logical_type': 'DECIMAL(9,2)', 'converted_type': 'DECIMAL', 'type_length': None, 'precision': 9, 'scale': 2},
+        {'path': 'd64', 'logical_type': 'DECIMAL(18,4)', 'converted_type': 'DECIMAL', 'type_length': None, 'precision': 18, 'scale': 4},
+        {'path': 'dfixed', 'logical_type': 'DECIMAL(38,10)', 'converted_type': 'DECIMAL', 'type_length': 16, 'precision': 38, 'scale': 10},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select path, logical_type, type_length from parquet_columns('tests/data/logical.parquet') where physical_type != 'BYTE_ARRAY'"),
+      [
+        {'path': 'time_ms', 'logical_type': 'TIME(MILLIS,true)', 'type_length': None},
+        {'path': 'time_us', 'logical_type': 'TIME(MICROS,false)', 'type_length': None},
+        {'path': 'time_ns', 'logical_type': 'TIME(NANOS,false)', 'type_length': None},
+        {'path': 'duration', 'logical_type': None, 'type_length': 12},
+        {'path': 'id', 'logical_type': 'UUID', 'type_length': 16},
+      ]
+    )
+    # schemas compare with plain joins
+    self.assertEqual(
+      execute_all("""
+        select a.path, a.physical_type as a_type, b.physical_type as b_type
+        from parquet_columns('tests/data/union/a.parquet') a
+        left join parquet_columns('tests/data/union/b.parquet') b on b.path = a.path
+        where b.physical_type is not a.physical_type
+      """),
+      [
+        {'path': 'id', 'a_type': 'INT32', 'b_type': 'INT64'},
+        {'path': 'name', 'a_type': 'BYTE_ARRAY', 'b_type': None},
+        {'path': 'score', 'a_type': 'FLOAT', 'b_type': 'DOUBLE'},
+      ]
+    )
+
+  def test_parquet_column_chunks(self):
+    self.assertEqual(
+      execute_all("select count(*) as n, count(distinct row_group) as row_groups from parquet_column_chunks('tests/data/row_groups.parquet')"),
+      [{'n': 30, 'row_groups': 10}]
+    )
+    self.assertEqual(
+      execute_all("select column_name, num_values, stats_min, stats_max from parquet_column_chunks('tests/data/row_groups.parquet') where row_group = 3"),
+      [
+        {'column_name': '"id"', 'num_values': 10, 'stats_min': 30, 'stats_max': 39},
+        {'column_name': '"name"', 'num_values': 10, 'stats_min': 'name030', 'stats_max': 'name039'},
+        {'column_name': '"value"', 'num_values': 10, 'stats_min': 15.0, 'stats_max': 19.5},
+      ]
+    )
+
+    chunks = execute_all("""
+      select
+        encodings,
+        compression,
+        dictionary_page_offset < data_page_offset as dictionary_first,
+        file_path,
+        compression_ratio = uncompressed_size * 1.0 / compressed_size as ratio
+      from parquet_column_chunks('tests/data/row_groups.parquet')
+    """)
+    self.assertEqual(len(chunks), 30)
+    for chunk in chunks:
+      self.assertIn('RLE_DICTIONARY', json.loads(chunk['encodings']))
+      self.assertIn(chunk['compression'], ['UNCOMPRESSED', 'SNAPPY', 'GZIP', 'ZSTD', 'LZ4_RAW'])
+      self.assertEqual(
+        {k: chunk[k] for k in ('dictionary_first', 'file_path', 'ratio')},
+        {'dictionary_first': 1, 'file_path': None, 'ratio': 1},
+      )
+    # the first chunk's dictionary is right after the magic number
+    self.assertEqual(
+      execute_all("select min(dictionary_page_offset) as offset from parquet_column_chunks('tests/data/row_groups.parquet')"),
+      [{'offset': 4}]
+    )
+
+  def test_parquet_column_chunks_stats(self):
+    def stats(path, *columns):
+      return [
+        (row['column_name'], row['stats_min'], row['stats_max'])
+        for row in execute_all(f"select column_name, stats_min, stats_max from parquet_column_chunks('{path}')")
+        if row['column_name'].strip('"') in columns
+      ]
+
+    # logical types read like the parquet table reads them
+    self.assertEqual(stats('tests/data/decimals.parquet', 'd32', 'dfixed'), [
+      ('"d32"', '-0.05', '123.45'),
+      ('"dfixed"', '-0.0000000001', '1234567890123456789012345678.9012345678'),
+    ])
+    self.assertEqual(stats('tests/data/timestamps.parquet', 'ts_millis'), [
+      ('"ts_millis"', '1969-12-31 23:59:59.500Z', '2022-10-25 12:34:56.789Z'),
+    ])
+    self.assertEqual(stats('tests/data/logical.parquet', 'time_ms', 'id', 'color'), [
+      ('"time_ms"', '00:00:00.000', '12:34:56.789'),
+      ('"id"', '00000000-0000-0000-0000-000000000000', 'f81d4fae-7dec-11d0-a765-00a0c91e6bf6'),
+      ('"color"', 'green', 'red'),
+    ])
+    self.assertEqual(stats('tests/data/numbers.parquet', 'bools')[0], ('"bools"', 0, 1))
+
+    # text that isn't valid UTF-8 comes back as a blob
+    self.assertEqual(stats('tests/data/invalid_utf8.parquet', 'name'), [('"name"', 'abc', b'\xff\xfe')])
+
+    self.assertEqual(
+      execute_all("select distinct stats_deprecated from parquet_column_chunks('tests/data/row_groups.parquet')"),
+      [{'stats_deprecated': 0}]
+    )
+
+  def test_parquet_pages(self):
+    pages = execute_all("select row_group, column, page, page_type, num_values, offset from parquet_pages('tests/data/row_groups.parquet')")
+    # every column chunk starts with its dictionary
+    self.assertEqual(
+      {(page['page'], page['page_type']) for page in pages if page['page'] == 0},
+      {(0, 'DICTIONARY_PAGE')}
+    )
+    self.assertEqual([page['offset'] for page in pages], sorted(page['offset'] for page in pages))
+
+    # the data pages have all of a chunk's values, and the pages and their
+    # headers all of its bytes
+    self.assertEqual(
+      execute_all("""
+        select
+          count(*) as n,
+          sum(p.num_values = c.num_values) as values_match,
+          sum(p.compressed_size < c.compressed_size) as sizes_fit
+        from parquet_column_chunks('tests/data/row_groups.parquet') c
+        join (
+          select row_group, column, sum(num_values) filter (where page_type like 'DATA_PAGE%') as num_values, sum(compressed_size) as compressed_size
+          from parquet_pages('tests/data/row_groups.parquet')
+          group by 1, 2
+        ) p on p.row_group = c.row_group and '"' || p.column || '"' = c.column_name
+      """),
+      [{'n': 30, 'values_match': 30, 'sizes_fit': 30}]
+    )
+
+    self.assertEqual(
+      execute_all("select encoding, stats_min, stats_max from parquet_pages('tests/data/row_groups.parquet') where row_group = 3 and column = 'id' and page_type = 'DATA_PAGE'"),
+      [{'encoding': 'RLE_DICTIONARY', 'stats_min': 30, 'stats_max': 39}]
+    )
+    # stats read like the column's values
+    self.assertEqual(
+      execute_all("select stats_min, stats_max from parquet_pages('tests/data/logical.parquet') where column = 'id'"),
+      [{'stats_min': '00000000-0000-0000-0000-000000000000', 'stats_max': 'f81d4fae-7dec-11d0-a765-00a0c91e6bf6'}]
+    )
+    with open('tests/data/row_groups.parquet', 'rb') as f:
+      blob = f.read()
+    self.assertEqual(execute_all("select count(*) as n from parquet_pages(?)", [blob]), [{'n': len(pages)}])
+
+  def test_parquet_page_index(self):
+    db.execute("create virtual table page_index using parquet(filename='tests/data/page_index.parquet')")
+    db.execute("select parquet_trace('debug')")
+    last = db.execute("select coalesce(max(rowid), 0) from parquet_trace_log").fetchone()[0]
+    self.assertEqual(
+      execute_all("select rowid, id, name from page_index where id between 250 and 252"),
+      [
+        {'rowid': 250, 'id': 250, 'name': 'name-0250'},
+        {'rowid': 251, 'id': 251, 'name': 'name-0251'},
+        {'rowid': 252, 'id': 252, 'name': 'name-0252'},
+      ]
+    )
+    # only the pages with ids in range are read, of both columns
+    self.assertEqual(
+      execute_all("select event, message from parquet_trace_log where event in ('prune pages', 'read row group') and rowid > ?", [last]),
+      [
+        {'event': 'prune pages', 'message': 'reading 2 of 20 pages, 100 of 1000 rows'},
+        {'event': 'read row group', 'message': 'skipped 900 rows'},
+      ]
+    )
+    db.execute("select parquet_trace('off')")
+
+    # pages of columns that aren't constrained still line up with the rows read
+    self.assertEqual(
+      execute_all("select rowid, id, note from page_index where id in (499, 650) or (id > 995 and note is not null)"),
+      [
+        {'rowid': 499, 'id': 499, 'note': 'note 4'},
+        {'rowid': 650, 'id': 650, 'note': 'note 6'},
+        {'rowid': 996, 'id': 996, 'note': 'note 9'},
+        {'rowid': 997, 'id': 997, 'note': 'note 9'},
+        {'rowid': 998, 'id': 998, 'note': 'note 9'},
+        {'rowid': 999, 'id': 999, 'note': 'note 9'},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n, min(id) as first, max(rowid) as last from page_index where note is null"),
+      [{'n': 100, 'first': 500, 'last': 599}]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n, sum(bucket) as buckets from page_index where note = 'note 3' and bucket = 4"),
+      [{'n': 10, 'buckets': 40}]
+    )
+    self.assertEqual(execute_all("select id from page_index where id < 0 or name = 'nope'"), [])
+    # page min/max are compared byte by byte, so other collations read every page
+    self.assertEqual(
+      execute_all("select rowid, name from page_index where name = 'NAME-0250' collate nocase"),
+      [{'rowid': 250, 'name': 'name-0250'}]
+    )
+    # rowid ranges skip pages too, even with no columns to read
+    self.assertEqual(
+      execute_all("select count(*) as n, min(id) as first from page_index where rowid between 120 and 130"),
+      [{'n': 11, 'first': 120}]
+    )
+    self.assertEqual(execute_all("select count(*) as n from page_index where rowid between 120 and 130"), [{'n': 11}])
+
+  def test_parquet_bloom_filter(self):
+    db.execute("create virtual table bloom using parquet(filename='tests/data/bloom.parquet')")
+    # every row group's min/max span the same values, only bloom filters tell them apart
+    db.execute("select parquet_trace('debug')")
+    last = db.execute("select coalesce(max(rowid), 0) from parquet_trace_log").fetchone()[0]
+    self.assertEqual(
+      execute_all("select rowid, id, user_id, account, score from bloom where user_id = 'user-01234'"),
+      [{'rowid': 660, 'id': 660, 'user_id': 'user-01234', 'account': 101234, 'score': 308.5}]
+    )
+    self.assertEqual(
+      execute_all("select event, row_group, bytes, message from parquet_trace_log where event = 'check bloom filters' and rowid > ?", [last]),
+      [
+        {'event': 'check bloom filters', 'row_group': 0, 'bytes': 2048, 'message': 'the row group may match'},
+        {'event': 'check bloom filters', 'row_group': 1, 'bytes': 2048, 'message': 'skipped the row group'},
+        {'event': 'check bloom filters', 'row_group': 2, 'bytes': 2048, 'message': 'skipped the row group'},
+        {'event': 'check bloom filters', 'row_group': 3, 'bytes': 2048, 'message': 'skipped the row group'},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n from parquet_trace_log where event = 'read row group' and rowid > ?", [last]),
+      [{'n': 1}]
+    )
+    db.execute("select parquet_trace('off')")
+
+    # each value of an IN list is checked on its own
+    self.assertEqual(
+      execute_all("select rowid, account from bloom where account in (100000, 101234, 104000, 99999) order by rowid"),
+      [
+        {'rowid': 0, 'account': 100000},
+        {'rowid': 660, 'account': 101234},
+        {'rowid': 1880, 'account': 104000},
+      ]
+    )
+    self.assertEqual(execute_all("select rowid from bloom where score = 308.5 and account = 101234.0"), [{'rowid': 660}])
+    self.assertEqual(execute_all("select rowid from bloom where user_id = 'user-01234x'"), [])
+    # bloom filters hash the exact bytes, so they can't rule out other collations
+    self.assertEqual(execute_all("select rowid from bloom where user_id = 'USER-01234' collate nocase"), [{'rowid': 660}])
+    self.assertEqual(execute_all("select rowid from bloom where user_id = 'user-01234' and account = 101235"), [])
+    # other constraints are left to the statistics and SQLite
+    self.assertEqual(execute_all("select count(*) as n from bloom where user_id >= 'user-03999'"), [{'n': 2}])
+    self.assertEqual(execute_all("select count(*) as n from bloom where account = '101234'"), [{'n': 1}])
+
+  def test_parquet_column_index(self):
+    self.assertEqual(
+      execute_all("select row_group, column, page, null_page, min, max, null_count from parquet_column_index('tests/data/page_index.parquet') where column in ('id', 'name') and page in (0, 9)"),
+      [
+        {'row_group': 0, 'column': 'id', 'page': 0, 'null_page': 0, 'min': 0, 'max': 99, 'null_count': 0},
+        {'row_group': 0, 'column': 'id', 'page': 9, 'null_page': 0, 'min': 900, 'max': 999, 'null_count': 0},
+        {'row_group': 0, 'column': 'name', 'page': 0, 'null_page': 0, 'min': 'name-0000', 'max': 'name-0099', 'null_count': 0},
+        {'row_group': 0, 'column': 'name', 'page': 9, 'null_page': 0, 'min': 'name-0900', 'max': 'name-0999', 'null_count': 0},
+      ]
+    )
+    self.assertEqual(
+      execute_all("select count(*) as n, sum(null_count) as nulls, count(distinct boundary_order) as orders from parquet_column_index('tests/data/page_index.parquet') where column = 'note'"),
+      [{'n': 9, 'nulls': 100, 'orders': 1}]
+    )
+    # min and max read like the column's values
+    self.assertEqual(
+      execute_all("select min, max from parquet_column_index('tests/data/uint64.parquet') where column = 'big'"),
+      [{'min': 1, 'max': '18446744073709551615'}]
+    )
+    self.assertEqual(
+      execute_all("select max from parquet_column_index('tests/data/uint64.parquet', 'null') where column = 'big'"),
+      [{'max': None}]
+    )
+    with open('tests/data/page_index.parquet', 'rb') as f:
+      blob = f.read()
+    self.assertEqual(
+      execute_all("select count(*) as n from parquet_column_index(?)", [blob]),
+      execute_all("select count(*) as n from parquet_column_index('tests/data/page_index.parquet')"),
+    )
+
+  def test_parquet_offset_index(self):
+    self.assertEqual(
+      execute_all("select row_group, column, page, first_row_index, num_rows from parquet_offset_index('tests/data/page_index.parquet') where column = 'id' and page < 3"),
+      [
+        {'row_group': 0, 'column': 'id', 'page': 0, 'first_row_index': 0, 'num_rows': 100},
+        {'row_group': 0, 'column': 'id', 'page': 1, 'first_row_index': 100, 'num_rows': 100},
+        {'row_group': 0, 'column': 'id', 'page': 2, 'first_row_index': 200, 'num_rows': 100},
+      ]
+    )
+    # the locations are the data pages parquet_pages finds
+    self.assertEqual(
+      execute_all("select column, page, offset from parquet_offset_index('tests/data/page_index.parquet') order by offset"),
+      execute_all("select column, row_number() over (partition by row_group, column order by offset) - 1 as page, offset from parquet_pages('tests/data/page_index.parquet') where page_type = 'DATA_PAGE' order by offset"),
+    )
+    self.assertEqual(
+      execute_all("select column, sum(num_rows) as n from parquet_offset_index('tests/data/row_groups.parquet') group by 1 order by 1"),
+      [{'column': 'id', 'n': 100}, {'column': 'name', 'n': 100}, {'column': 'value', 'n': 100}]
+    )
+
+  def test_parquet_trace(self):
+    self.assertEqual(db.execute("select parquet_trace()").fetchone()[0], 'off')
+    with self.assertRaisesRegex(sqlite3.OperationalError, "unknown trace level 'loud'"):
+      db.execute("select parquet_trace('loud')")
+
+    # other tests trace too, so only look at events after this
+    last = db.execute("select coalesce(max(rowid), 0) from parquet_trace_log").fetchone()[0]
+    db.execute("create virtual table trace_row_groups using parquet(filename='tests/data/row_groups.parquet')")
+    execute_all("select * from trace_row_groups where id < 5")
+    self.assertEqual(execute_all("select count(*) as n from parquet_trace_log where rowid > ?", [last]), [{'n': 0}])
+
+    self.assertEqual(db.execute("select parquet_trace('info')").fetchone()[0], 'info')
+    execute_all("select id from trace_row_groups where id between 10 and 25")
+    self.assertEqual(
+      execute_all("select level, event, path, row_group, message from parquet_trace_log where rowid > ?", [last]),
+      [
+        {'level': 'info', 'event': 'open file', 'path': 'tests/data/row_groups.parquet', 'row_group': None, 'message': '100 rows in 10 row groups'},
+        {'level': 'info', 'event': 'prune row groups', 'path': 'tests/data/row_groups.parquet', 'row_group': None, 'message': 'reading 2 of 10 row groups, 1 of 3 columns'},
+      ]
+    )
+
+    self.assertEqual(db.execute("select parquet_trace('debug')").fetchone()[0], 'debug')
+    last = db.execute("select max(rowid) from parquet_trace_log").fetchone()[0]
+    execute_all("select id from trace_row_groups where id between 10 and 25")
+    events = execute_all("select event, row_group, bytes > 0 as has_bytes, elapsed_us >= 0 as has_elapsed from parquet_trace_log where rowid > ?", [last])
+    self.assertEqual(
+      [e for e in events if e['event'] in ('read row group', 'decode row group')],
+      [
+        {'event': 'read row group', 'row_group': 1, 'has_bytes': 1, 'has_elapsed': None},
+        {'event': 'decode row group', 'row_group': 1, 'has_bytes': None, 'has_elapsed': 1},
+        {'event': 'read row group', 'row_group': 2, 'has_bytes': 1, 'has_elapsed': None},
+        {'event': 'decode row group', 'row_group': 2, 'has_bytes': None, 'has_elapsed': 1},
+      ]
+    )
+    self.assertEqual(db.execute("select parquet_trace('off')").fetchone()[0], 'off')
+
+  def test_parquet_trace_log(self):
+    self.assertEqual(
+      [row[1] for row in db.execute("pragma table_info(parquet_trace_log)").fetchall()],
+      ['time', 'level', 'event', 'path', 'row_group', 'bytes', 'elapsed_us', 'message']
+    )
+
+  def test_parquet_errors(self):
+    with self.assertRaisesRegex(sqlite3.OperationalError, "could not open parquet file 'tests/data/missing.parquet'"):
+      db.execute("create virtual table missing using parquet(filename='tests/data/missing.parquet')")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "need a filename"):
+      db.execute("create virtual table no_filename using parquet()")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "error reading parquet file 'tests/data/not_parquet.parquet': invalid file"):
+      db.execute("create virtual table not_parquet using parquet(filename='tests/data/not_parquet.parquet')")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "error reading parquet file 'tests/data/truncated.parquet': invalid file"):
+      db.execute("create virtual table truncated using parquet(filename='tests/data/truncated.parquet')")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "error reading parquet file 'tests/data/truncated.parquet'"):
+      db.execute("select * from parquet_metadata('tests/data/truncated.parquet')").fetchall()
+    with self.assertRaisesRegex(sqlite3.OperationalError, "error reading parquet file 'tests/data/not_parquet.parquet'"):
+      db.execute("select * from parquet_column_chunks('tests/data/not_parquet.parquet')").fetchall()
+
+    # the footer is fine, so only reading values fails
+    db.execute("create virtual table corrupt_pages using parquet(filename='tests/data/corrupt_pages.parquet')")
+    self.assertEqual(execute_all("select count(*) as n from corrupt_pages"), [{'n': 100}])
+    with self.assertRaisesRegex(sqlite3.OperationalError, "error reading parquet file 'tests/data/corrupt_pages.parquet': could not decode row group 0"):
+      db.execute("select id from corrupt_pages").fetchall()
+    # the caught panic isn't reported on the host's stderr
+    stderr = os.dup(2)
+    with tempfile.TemporaryFile() as captured:
+      os.dup2(captured.fileno(), 2)
+      try:
+        with self.assertRaises(sqlite3.OperationalError):
+          db.execute("select id from corrupt_pages").fetchall()
+      finally:
+        os.dup2(stderr, 2)
+        os.close(stderr)
+      captured.seek(0)
+      self.assertEqual(captured.read(), b"")
+    with self.assertRaisesRegex(sqlite3.OperationalError, "error reading parquet file 'tests/data/corrupt_pages.parquet': .*could not read page header at 4"):
+      db.execute("select * from parquet_pages('tests/data/corrupt_pages.parquet')").fetchall()
+    # the connection is still usable afterwards
+    self.assertEqual(db.execute("select 1").fetchone()[0], 1)
     
   
 class TestCoverage(unittest.TestCase):                                      